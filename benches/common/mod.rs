@@ -0,0 +1,28 @@
+// Shared by every benchmark, each of which only uses some of it
+#![allow(dead_code)]
+
+use glam::Vec3;
+
+/// Side of the cube, centered in the border, that benchmark bodies are spread over.
+const SIDE: f32 = 0.9;
+
+/// `count` positions on a lattice filling a cube inside the border, along with the spacing between neighbours.
+pub fn lattice(count: usize) -> (impl Iterator<Item = Vec3>, f32) {
+    let per_side = (count as f32).cbrt().ceil() as usize;
+    let spacing = SIDE / per_side as f32;
+    let positions = (0..count).map(move |i| {
+        let cell = Vec3::new(
+            (i % per_side) as f32,
+            (i / per_side % per_side) as f32,
+            (i / per_side / per_side) as f32,
+        );
+        (cell + 0.5) * spacing - SIDE / 2.0
+    });
+    (positions, spacing)
+}
+
+/// A different direction for every index, so bodies don't all move the same way.
+pub fn velocity(i: usize) -> Vec3 {
+    let angle = i as f32 * 2.4;
+    Vec3::new(angle.cos(), (angle * 0.7).sin(), angle.sin())
+}
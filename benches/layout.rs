@@ -0,0 +1,89 @@
+//! Compares the array of structs layout of `Scene::physics_bodies` against `BodyStore` on the same kernels.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use silly_goose::physics::{BodyStore, PhysicsBody, GRAVITY};
+use silly_goose::DT;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// `count` bodies on a lattice, each overlapping its neighbours a little and moving in its own direction.
+fn bodies(count: usize) -> Vec<PhysicsBody> {
+    let (positions, spacing) = common::lattice(count);
+    positions
+        .enumerate()
+        .map(|(i, pos)| {
+            let mut body = PhysicsBody::new(pos, 0.55 * spacing);
+            body.velocity = common::velocity(i);
+            body
+        })
+        .collect()
+}
+
+fn integrate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("integrate");
+    for count in SIZES {
+        group.throughput(Throughput::Elements(count as u64));
+        let mut aos = bodies(count);
+        group.bench_function(BenchmarkId::new("aos", count), |b| {
+            b.iter(|| {
+                for body in &mut aos {
+                    body.velocity += GRAVITY * DT;
+                    body.pos += body.velocity * DT;
+                }
+            })
+        });
+        let mut soa = BodyStore::from_bodies(&bodies(count));
+        group.bench_function(BenchmarkId::new("soa", count), |b| {
+            b.iter(|| soa.integrate(GRAVITY, DT))
+        });
+    }
+}
+
+fn border(c: &mut Criterion) {
+    let mut group = c.benchmark_group("border");
+    for count in SIZES {
+        group.throughput(Throughput::Elements(count as u64));
+        let aos = bodies(count);
+        group.bench_function(BenchmarkId::new("aos", count), |b| {
+            b.iter_batched_ref(
+                || aos.clone(),
+                |aos| aos.iter_mut().for_each(PhysicsBody::keep_within_border),
+                BatchSize::LargeInput,
+            )
+        });
+        let soa = BodyStore::from_bodies(&aos);
+        group.bench_function(BenchmarkId::new("soa", count), |b| {
+            b.iter_batched_ref(|| soa.clone(), BodyStore::keep_within_border, BatchSize::LargeInput)
+        });
+    }
+}
+
+fn contacts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contacts");
+    for count in SIZES {
+        let aos = bodies(count);
+        let soa = BodyStore::from_bodies(&aos);
+        let pairs = soa.nearby_pairs(0.0);
+        group.throughput(Throughput::Elements(pairs.len() as u64));
+        group.bench_function(BenchmarkId::new("aos", count), |b| {
+            b.iter_batched_ref(
+                || aos.clone(),
+                |aos| {
+                    for &(a, b) in &pairs {
+                        let (first, rest) = aos.split_at_mut(a.max(b));
+                        first[a.min(b)].collide_with(&mut rest[0]);
+                    }
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("soa", count), |b| {
+            b.iter_batched_ref(|| soa.clone(), |soa| soa.collide_pairs(&pairs), BatchSize::LargeInput)
+        });
+    }
+}
+
+criterion_group!(benches, integrate, border, contacts);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+//! Building sphere meshes and getting them ready to upload each frame.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use glam::{Vec3, Vec4};
+use silly_goose::physics::{Mesh, Scene};
+use silly_goose::{BORDER_CENTER, BORDER_RADIUS};
+use std::hint::black_box;
+
+const SUBDIVISIONS: [u32; 5] = [4, 8, 16, 32, 64];
+const BALL_COUNTS: [usize; 3] = [100, 1_000, 10_000];
+
+fn scene(balls: usize) -> Scene {
+    let mut scene = Scene::default();
+    scene.create_3d_border(BORDER_RADIUS, 5, BORDER_CENTER);
+    let (positions, spacing) = common::lattice(balls);
+    for pos in positions {
+        scene.add_ball(0.4 * spacing, pos, Vec4::ONE);
+    }
+    scene
+}
+
+fn sphere(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sphere");
+    for subdivisions in SUBDIVISIONS {
+        let vertices = (subdivisions + 1) * (2 * subdivisions + 1);
+        group.throughput(Throughput::Elements(vertices as u64));
+        group.bench_function(BenchmarkId::from_parameter(subdivisions), |b| {
+            b.iter(|| Mesh::sphere(0.05, black_box(subdivisions), Vec3::ZERO, Vec4::ONE))
+        });
+    }
+}
+
+fn update_dynamic_vertices(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_dynamic_vertices");
+    for balls in BALL_COUNTS {
+        group.throughput(Throughput::Elements(balls as u64));
+        let mut scene = scene(balls);
+        group.bench_function(BenchmarkId::from_parameter(balls), |b| {
+            b.iter(|| {
+                // Moved every time, so the vertices always have somewhere new to go
+                for body in &mut scene.physics_bodies {
+                    body.pos.x = -body.pos.x;
+                }
+                scene.update_dynamic_vertices();
+            })
+        });
+    }
+}
+
+/// Collecting every mesh into the single vertex and index lists uploaded to the GPU.
+fn flatten(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flatten");
+    for balls in BALL_COUNTS {
+        let scene = scene(balls);
+        group.throughput(Throughput::Elements(balls as u64));
+        group.bench_function(BenchmarkId::new("dynamic_vertices", balls), |b| {
+            b.iter(|| scene.dynamic_vertices())
+        });
+        group.bench_function(BenchmarkId::new("dynamic_indices", balls), |b| {
+            b.iter(|| scene.dynamic_indices())
+        });
+    }
+    // The border is the same whatever the number of balls
+    let scene = scene(0);
+    group.throughput(Throughput::Elements(scene.static_meshes.len() as u64));
+    group.bench_function("static_vertices", |b| b.iter(|| scene.static_vertices()));
+    group.bench_function("static_indices", |b| b.iter(|| scene.static_indices()));
+}
+
+criterion_group!(benches, sphere, update_dynamic_vertices, flatten);
+criterion_main!(benches);
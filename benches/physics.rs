@@ -0,0 +1,45 @@
+//! Whole physics steps, at different numbers of bodies and with bodies spread out or packed together.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use silly_goose::physics::Scene;
+use silly_goose::{BORDER_CENTER, BORDER_RADIUS, DT};
+
+const SIZES: [usize; 3] = [100, 1_000, 4_000];
+
+/// Radius of the bodies as a fraction of the spacing between them. Sparse bodies rarely touch, dense ones start out
+/// overlapping every neighbour.
+const DENSITIES: [(&str, f32); 2] = [("sparse", 0.15), ("dense", 0.55)];
+
+fn scene(count: usize, radius_fraction: f32) -> Scene {
+    let mut scene = Scene::default();
+    scene.create_3d_border(BORDER_RADIUS, 5, BORDER_CENTER);
+    let (positions, spacing) = common::lattice(count);
+    for (i, pos) in positions.enumerate() {
+        let ball = scene.add_ball(radius_fraction * spacing, pos, glam::Vec4::ONE);
+        scene.physics_bodies[ball].velocity = common::velocity(i);
+    }
+    scene
+}
+
+fn update_physics(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update_physics");
+    group.sample_size(20);
+    for (density, radius_fraction) in DENSITIES {
+        for count in SIZES {
+            group.throughput(Throughput::Elements(count as u64));
+            let scene = scene(count, radius_fraction);
+            group.bench_function(BenchmarkId::new(density, count), |b| {
+                b.iter_batched_ref(
+                    || scene.clone(),
+                    |scene| scene.update_physics(DT),
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+}
+
+criterion_group!(benches, update_physics);
+criterion_main!(benches);
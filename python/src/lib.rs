@@ -0,0 +1,224 @@
+use glam::Vec3;
+use numpy::ndarray::{Array1, Array2};
+use numpy::{AllowTypeChange, IntoPyArray, PyArray1, PyArray2, PyArrayLike2};
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use silly_goose::demos::bordered_scene;
+use silly_goose::physics::{Mass, Scene, Solver, Xpbd};
+use silly_goose::scene_file::{self, SceneFileError};
+use silly_goose::{BORDER_CENTER, BORDER_RADIUS, DT};
+use std::path::PathBuf;
+
+/// Balls inside the spherical border, simulated without a window. Body state is read and written as NumPy arrays,
+/// one row per ball in the order they were added.
+#[pyclass(name = "Scene", module = "silly_goose")]
+struct PyScene {
+    scene: Scene,
+}
+
+#[pymethods]
+impl PyScene {
+    /// An empty border. `gravity` defaults to 9.8 m/s² downwards, and `solver` is "impulse" or "xpbd".
+    #[new]
+    #[pyo3(signature = (gravity = None, solver = "impulse"))]
+    fn new(gravity: Option<[f32; 3]>, solver: &str) -> PyResult<Self> {
+        let mut scene = bordered_scene();
+        if let Some(gravity) = gravity {
+            scene.gravity = Vec3::from(gravity);
+        }
+        scene.solver = match solver {
+            "impulse" => Solver::Impulse,
+            "xpbd" => Solver::Xpbd(Xpbd::default()),
+            _ => return Err(PyValueError::new_err(format!("unknown solver {solver:?}"))),
+        };
+        Ok(Self { scene })
+    }
+
+    /// Loads a TOML scene file, the same as `--scene` does.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        match scene_file::load(&path) {
+            Ok(scene) => Ok(Self { scene }),
+            Err(e @ SceneFileError::Read(..)) => Err(PyOSError::new_err(e.to_string())),
+            Err(e) => Err(PyValueError::new_err(e.to_string())),
+        }
+    }
+
+    /// Adds a ball and returns its index. At most one of `mass` in kilograms, `density` in kilograms per cubic metre
+    /// and `immovable` can be given, and without any the ball weighs one kilogram.
+    #[pyo3(signature = (radius, pos, velocity = [0.0; 3], *, mass = None, density = None, immovable = false))]
+    fn add_ball(
+        &mut self,
+        radius: f32,
+        pos: [f32; 3],
+        velocity: [f32; 3],
+        mass: Option<f32>,
+        density: Option<f32>,
+        immovable: bool,
+    ) -> PyResult<usize> {
+        if radius <= 0.0 {
+            return Err(PyValueError::new_err("radius must be positive"));
+        }
+        let mass = match (mass, density, immovable) {
+            (None, None, false) => Mass::Kilograms(1.0),
+            (Some(mass), None, false) if mass > 0.0 => Mass::Kilograms(mass),
+            (None, Some(density), false) if density > 0.0 => Mass::Density(density),
+            (None, None, true) => Mass::Immovable,
+            (Some(_), None, false) | (None, Some(_), false) => {
+                return Err(PyValueError::new_err("mass and density must be positive"))
+            }
+            _ => {
+                return Err(PyValueError::new_err(
+                    "only one of mass, density and immovable can be given",
+                ))
+            }
+        };
+        let ball = self.scene.add_ball_with_mass(radius, pos.into(), glam::Vec4::ONE, mass);
+        self.scene.physics_bodies[ball].velocity = velocity.into();
+        Ok(ball)
+    }
+
+    /// Advances the simulation by `steps` steps of `dt` seconds. Other Python threads carry on meanwhile, so scenes
+    /// in a parameter sweep can run side by side.
+    #[pyo3(signature = (steps = 1, dt = DT))]
+    fn update_physics(&mut self, py: Python<'_>, steps: usize, dt: f32) {
+        let scene = &mut self.scene;
+        py.detach(|| {
+            for _ in 0..steps {
+                scene.update_physics(dt);
+            }
+        });
+    }
+
+    /// Kinetic plus gravitational potential energy of every ball that can move, in joules.
+    fn total_energy(&self) -> f32 {
+        self.scene.total_energy()
+    }
+
+    /// Centers of the balls, as an array of shape (balls, 3).
+    #[getter]
+    fn positions<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f32>> {
+        vec3_array(py, self.scene.physics_bodies.iter().map(|b| b.pos))
+    }
+
+    /// Moves every ball. Balls that were asleep wake up.
+    #[setter]
+    fn set_positions(&mut self, positions: PyArrayLike2<f32, AllowTypeChange>) -> PyResult<()> {
+        let positions = read_vec3s(positions, self.scene.physics_bodies.len())?;
+        for (b, pos) in self.scene.physics_bodies.iter_mut().zip(positions) {
+            b.pos = pos;
+        }
+        self.scene.wake_all();
+        Ok(())
+    }
+
+    /// Velocities of the balls in metres per second, as an array of shape (balls, 3).
+    #[getter]
+    fn velocities<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f32>> {
+        vec3_array(py, self.scene.physics_bodies.iter().map(|b| b.velocity))
+    }
+
+    /// Changes the velocity of every ball. Balls that were asleep wake up.
+    #[setter]
+    fn set_velocities(&mut self, velocities: PyArrayLike2<f32, AllowTypeChange>) -> PyResult<()> {
+        let velocities = read_vec3s(velocities, self.scene.physics_bodies.len())?;
+        for (b, velocity) in self.scene.physics_bodies.iter_mut().zip(velocities) {
+            b.velocity = velocity;
+        }
+        self.scene.wake_all();
+        Ok(())
+    }
+
+    #[getter]
+    fn radii<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f32>> {
+        Array1::from_iter(self.scene.physics_bodies.iter().map(|b| b.radius)).into_pyarray(py)
+    }
+
+    /// Masses in kilograms, infinite for immovable balls.
+    #[getter]
+    fn masses<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<f32>> {
+        Array1::from_iter(self.scene.physics_bodies.iter().map(|b| b.mass)).into_pyarray(py)
+    }
+
+    #[getter]
+    fn asleep<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray1<bool>> {
+        Array1::from_iter(self.scene.physics_bodies.iter().map(|b| b.asleep)).into_pyarray(py)
+    }
+
+    #[getter]
+    fn gravity(&self) -> [f32; 3] {
+        self.scene.gravity.to_array()
+    }
+
+    /// Changes gravity, waking every ball since resting balls don't notice it changing.
+    #[setter]
+    fn set_gravity(&mut self, gravity: [f32; 3]) {
+        self.scene.gravity = gravity.into();
+        self.scene.wake_all();
+    }
+
+    /// Simulated seconds since the scene was created.
+    #[getter]
+    fn time(&self) -> f32 {
+        self.scene.time
+    }
+
+    /// Physics steps taken since the scene was created.
+    #[getter]
+    fn steps(&self) -> u64 {
+        self.scene.steps
+    }
+
+    /// Number of touching pairs found during the last step.
+    #[getter]
+    fn contacts(&self) -> usize {
+        self.scene.contacts
+    }
+
+    fn __len__(&self) -> usize {
+        self.scene.physics_bodies.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Scene({} balls, {} s, {} steps)",
+            self.scene.physics_bodies.len(),
+            self.scene.time,
+            self.scene.steps
+        )
+    }
+}
+
+fn vec3_array<'py>(py: Python<'py>, values: impl Iterator<Item = Vec3>) -> Bound<'py, PyArray2<f32>> {
+    let components = values.flat_map(|v| v.to_array()).collect::<Vec<_>>();
+    Array2::from_shape_vec((components.len() / 3, 3), components)
+        .expect("three components per row")
+        .into_pyarray(py)
+}
+
+/// Rows of `array` as vectors, which must have a row for each of `count` balls.
+fn read_vec3s(array: PyArrayLike2<f32, AllowTypeChange>, count: usize) -> PyResult<Vec<Vec3>> {
+    let array = array.as_array();
+    if array.shape() != [count, 3] {
+        return Err(PyValueError::new_err(format!(
+            "expected shape ({count}, 3), not {:?}",
+            array.shape()
+        )));
+    }
+    Ok(array
+        .rows()
+        .into_iter()
+        .map(|row| Vec3::new(row[0], row[1], row[2]))
+        .collect())
+}
+
+/// Headless ball physics from silly-goose.
+#[pymodule]
+#[pyo3(name = "silly_goose")]
+fn silly_goose_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyScene>()?;
+    m.add("DT", DT)?;
+    m.add("BORDER_RADIUS", BORDER_RADIUS)?;
+    m.add("BORDER_CENTER", BORDER_CENTER.to_array())?;
+    Ok(())
+}
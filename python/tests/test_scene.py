@@ -0,0 +1,76 @@
+from pathlib import Path
+
+import numpy as np
+import pytest
+
+import silly_goose as sg
+
+SCENES = Path(__file__).parents[2] / "scenes"
+
+
+def test_ball_bounces_off_the_floor():
+    scene = sg.Scene()
+    scene.add_ball(0.05, (0.0, 0.5, 0.0))
+    lowest = np.inf
+    for _ in range(1000):
+        scene.update_physics()
+        lowest = min(lowest, scene.positions[0, 1])
+    floor = sg.BORDER_CENTER[1] - sg.BORDER_RADIUS + 0.05
+    assert lowest == pytest.approx(floor, abs=0.01)
+    assert scene.positions[0, 1] > floor + 0.5
+    assert scene.steps == 1000
+    assert scene.time == pytest.approx(1000 * sg.DT, rel=1e-3)
+
+
+def test_state_arrays_have_a_row_per_ball():
+    scene = sg.Scene(gravity=(0.0, 0.0, 0.0))
+    scene.add_ball(0.05, (0.0, 0.0, 0.0), density=1000.0)
+    scene.add_ball(0.1, (0.3, 0.0, 0.0), (1.0, 0.0, 0.0), mass=2.0)
+    scene.add_ball(0.1, (-0.3, 0.0, 0.0), immovable=True)
+    assert len(scene) == 3
+    assert scene.positions.shape == (3, 3)
+    assert scene.velocities.dtype == np.float32
+    np.testing.assert_allclose(scene.radii, [0.05, 0.1, 0.1])
+    assert scene.masses[1] == 2.0
+    assert np.isinf(scene.masses[2])
+
+
+def test_setting_velocities_moves_balls():
+    scene = sg.Scene(gravity=(0.0, 0.0, 0.0), solver="xpbd")
+    for x in (-0.2, 0.2):
+        scene.add_ball(0.05, (x, 0.0, 0.0))
+    # Plain float64 arrays and lists are converted
+    scene.velocities = np.array([[0.0, 1.0, 0.0], [0.0, -1.0, 0.0]])
+    scene.update_physics(100)
+    np.testing.assert_allclose(scene.positions[:, 1], [0.1, -0.1], atol=1e-4)
+    with pytest.raises(ValueError):
+        scene.positions = [[0.0, 0.0, 0.0]]
+
+
+def test_gravity_sweep_orders_fall_times():
+    fallen = []
+    for g in (2.0, 5.0, 10.0):
+        scene = sg.Scene(gravity=(0.0, -g, 0.0))
+        scene.add_ball(0.05, (0.0, 0.0, 0.0))
+        scene.update_physics(200)
+        fallen.append(-scene.positions[0, 1])
+    assert fallen == sorted(fallen)
+
+
+def test_bad_arguments_raise():
+    scene = sg.Scene()
+    with pytest.raises(ValueError):
+        scene.add_ball(0.05, (0.0, 0.0, 0.0), mass=1.0, immovable=True)
+    with pytest.raises(ValueError):
+        scene.add_ball(-1.0, (0.0, 0.0, 0.0))
+    with pytest.raises(ValueError):
+        sg.Scene(solver="verlet")
+    with pytest.raises(OSError):
+        sg.Scene.load("missing.toml")
+
+
+def test_scene_files_load():
+    scene = sg.Scene.load(SCENES / "stirrer.toml")
+    assert len(scene) > 0
+    scene.update_physics(10)
+    assert np.isfinite(scene.positions).all()
@@ -0,0 +1,78 @@
+use glam::{Mat4, Vec2, Vec3};
+use winit::dpi::PhysicalSize;
+
+/// Orthographic camera looking down +z with y up, which keeps the framing the scene was laid out for: the unit square
+/// around the origin fills the height of the window.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    /// Half of the visible height in world units. The visible width follows from the aspect ratio.
+    pub half_height: f32,
+    pub aspect: f32,
+}
+
+impl Camera {
+    pub fn new(size: PhysicalSize<u32>) -> Self {
+        let mut camera = Self {
+            eye: Vec3::new(0., 0., -2.),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            half_height: 1.,
+            aspect: 1.,
+        };
+        camera.resize(size);
+        camera
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.aspect = size.width.max(1) as f32 / size.height.max(1) as f32;
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        (self.target - self.eye).normalize()
+    }
+
+    pub fn view_proj(&self) -> Mat4 {
+        let depth = 2. * self.eye.distance(self.target);
+        let half_width = self.half_height * self.aspect;
+        let proj = Mat4::orthographic_lh(-half_width, half_width, -self.half_height, self.half_height, 0., depth);
+        proj * Mat4::look_at_lh(self.eye, self.target, self.up)
+    }
+
+    /// Returns the origin and direction of the ray through `cursor`, given in pixels from the top left of a window of
+    /// `size`.
+    pub fn ray(&self, cursor: Vec2, size: PhysicalSize<u32>) -> (Vec3, Vec3) {
+        let ndc = Vec2::new(
+            cursor.x / size.width.max(1) as f32 * 2. - 1.,
+            1. - cursor.y / size.height.max(1) as f32 * 2.,
+        );
+        let inverse = self.view_proj().inverse();
+        let near = inverse.project_point3(ndc.extend(0.));
+        let far = inverse.project_point3(ndc.extend(1.));
+        (near, (far - near).normalize())
+    }
+
+    /// Where the ray through `cursor` crosses the plane facing the camera that contains `point`.
+    pub fn cursor_on_plane(&self, cursor: Vec2, size: PhysicalSize<u32>, point: Vec3) -> Vec3 {
+        let (origin, dir) = self.ray(cursor, size);
+        let normal = self.forward();
+        let t = (point - origin).dot(normal) / dir.dot(normal);
+        origin + dir * t
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+impl From<&Camera> for CameraUniform {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            view_proj: camera.view_proj().to_cols_array_2d(),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::physics::{Anchor, Constraint, ConstraintKind, Container, Mass, Scene};
+use crate::{BALL_RADIUS, BALL_START, BORDER_CENTER, BORDER_RADIUS, COULOMB};
+use glam::{Vec3, Vec4};
+
+/// Empty border with charges enabled, for spawning and throwing balls around.
+pub fn sandbox() -> Scene {
+    let mut scene = bordered_scene();
+    scene.electrostatics = Some(COULOMB);
+    scene.color_by_charge = true;
+
+    scene.add_ball(BALL_RADIUS, BALL_START, Vec4::new(1., 1., 0., 1.));
+    scene.add_ball(BALL_RADIUS, Vec3::new(0., 0., 0.), Vec4::new(1., 0., 0., 1.));
+    scene
+}
+
+/// Five touching pendulums with the first one pulled back.
+pub fn newtons_cradle() -> Scene {
+    const BALLS: usize = 5;
+    const RADIUS: f32 = 0.06;
+    const LENGTH: f32 = 0.45;
+    const PIVOT_HEIGHT: f32 = 0.45;
+
+    let mut scene = bordered_scene();
+    for i in 0..BALLS {
+        let x = (i as f32 - (BALLS - 1) as f32 / 2.) * 2. * RADIUS;
+        let pivot = Vec3::new(x, PIVOT_HEIGHT, 0.);
+        let angle = if i == 0 { std::f32::consts::FRAC_PI_4 } else { 0. };
+        let pos = pivot + LENGTH * Vec3::new(-angle.sin(), -angle.cos(), 0.);
+
+        let ball = scene.add_ball(RADIUS, pos, Vec4::new(0.75, 0.75, 0.8, 1.));
+        scene.constraints.push(Constraint::new(
+            Anchor::Fixed(pivot),
+            Anchor::Body(ball),
+            ConstraintKind::Distance { length: LENGTH },
+        ));
+    }
+    scene
+}
+
+/// A chain of rigid links hanging from one end, next to a ball on a rope.
+pub fn chain() -> Scene {
+    const LINKS: usize = 12;
+    const RADIUS: f32 = 0.025;
+    const SPACING: f32 = 0.06;
+
+    let mut scene = bordered_scene();
+    let start = Vec3::new(-0.5, 0.5, 0.);
+    let mut previous = Anchor::Fixed(start);
+    for i in 1..=LINKS {
+        let link = scene.add_ball(
+            RADIUS,
+            start + Vec3::X * SPACING * i as f32,
+            Vec4::new(0.9, 0.7, 0.2, 1.),
+        );
+        scene.constraints.push(Constraint::new(
+            previous,
+            Anchor::Body(link),
+            ConstraintKind::Distance { length: SPACING },
+        ));
+        previous = Anchor::Body(link);
+    }
+
+    let pivot = Vec3::new(0.4, 0.5, 0.);
+    let ball = scene.add_ball(
+        BALL_RADIUS * 2.,
+        pivot + Vec3::new(0.2, -0.1, 0.),
+        Vec4::new(0.3, 0.6, 1., 1.),
+    );
+    scene.constraints.push(Constraint::new(
+        Anchor::Fixed(pivot),
+        Anchor::Body(ball),
+        ConstraintKind::Rope { length: 0.4 },
+    ));
+    scene
+}
+
+/// A cube of balls held together by springs, dropped from the top of the border.
+pub fn soft_body() -> Scene {
+    const SIDE: usize = 3;
+    const SPACING: f32 = 0.12;
+    const STIFFNESS: f32 = 400.;
+    const DAMPING: f32 = 2.;
+
+    let mut scene = bordered_scene();
+    let corner = Vec3::new(-0.12, 0.3, -0.12);
+    let mut balls = Vec::new();
+    for x in 0..SIDE {
+        for y in 0..SIDE {
+            for z in 0..SIDE {
+                let pos = corner + Vec3::new(x as f32, y as f32, z as f32) * SPACING;
+                balls.push((scene.add_ball(BALL_RADIUS, pos, Vec4::new(0.4, 0.9, 0.5, 1.)), pos));
+            }
+        }
+    }
+
+    // Connecting every ball to all neighbours, diagonals included, keeps the cube from shearing flat
+    for (n, &(a, pos_a)) in balls.iter().enumerate() {
+        for &(b, pos_b) in &balls[n + 1..] {
+            let rest_length = pos_a.distance(pos_b);
+            if rest_length < SPACING * 1.8 {
+                let spring = ConstraintKind::Spring {
+                    rest_length,
+                    stiffness: STIFFNESS,
+                    damping: DAMPING,
+                };
+                scene
+                    .constraints
+                    .push(Constraint::new(Anchor::Body(a), Anchor::Body(b), spring));
+            }
+        }
+    }
+    scene
+}
+
+/// Thousands of small balls filling the border, for the GPU physics path.
+pub fn ball_pit() -> Scene {
+    const SIDE: i32 = 18;
+    const RADIUS: f32 = 0.015;
+    const SPACING: f32 = 0.05;
+
+    let mut scene = bordered_scene();
+    for x in -SIDE / 2..SIDE / 2 {
+        for y in -SIDE / 2..SIDE / 2 {
+            for z in -SIDE / 2..SIDE / 2 {
+                let pos = BORDER_CENTER + Vec3::new(x as f32, y as f32, z as f32) * SPACING;
+                let hue = (x + SIDE / 2) as f32 / SIDE as f32;
+                scene.add_ball(RADIUS, pos, Vec4::new(hue, 0.5, 1. - hue, 1.));
+            }
+        }
+    }
+    scene
+}
+
+/// Light and heavy balls dropped through staggered rows of pegs that don't move.
+pub fn pegs() -> Scene {
+    const ROWS: i32 = 4;
+    const PEG_RADIUS: f32 = 0.04;
+    const PEG_SPACING: f32 = 0.22;
+    const DENSITY: f32 = 1000.;
+
+    let mut scene = bordered_scene();
+    for row in 0..ROWS {
+        let stagger = if row % 2 == 0 { 0. } else { PEG_SPACING / 2. };
+        for column in -2..=2 {
+            let pos = Vec3::new(column as f32 * PEG_SPACING + stagger, 0.1 - row as f32 * 0.15, 0.);
+            scene.add_ball_with_mass(PEG_RADIUS, pos, Vec4::new(0.6, 0.6, 0.65, 1.), Mass::Immovable);
+        }
+    }
+    for i in 0..8 {
+        let pos = Vec3::new(i as f32 * 0.09 - 0.3, 0.45, (i % 3) as f32 * 0.03 - 0.03);
+        if i % 2 == 0 {
+            scene.add_ball_with_mass(0.025, pos, Vec4::new(0.3, 0.7, 1., 1.), Mass::Density(DENSITY));
+        } else {
+            scene.add_ball_with_mass(0.04, pos, Vec4::new(1., 0.5, 0.2, 1.), Mass::Density(DENSITY * 8.));
+        }
+    }
+    scene
+}
+
+/// A drum turning on its axle, tumbling the balls at its bottom over each other like a mixer.
+pub fn tumbler() -> Scene {
+    const RADIUS: f32 = 0.05;
+
+    let mut scene = bordered_scene();
+    scene.container = Container {
+        spin: Vec3::Z * 1.5,
+        friction: 0.6,
+        ..Container::default()
+    };
+    for x in -3..=3 {
+        for y in 0..3 {
+            for z in -2..=2 {
+                let pos = BORDER_CENTER + Vec3::new(x as f32 * 0.11, y as f32 * 0.11 - 0.6, z as f32 * 0.11);
+                let color = if (x + y + z) % 2 == 0 {
+                    Vec4::new(0.9, 0.8, 0.2, 1.)
+                } else {
+                    Vec4::new(0.3, 0.5, 0.9, 1.)
+                };
+                scene.add_ball(RADIUS, pos, color);
+            }
+        }
+    }
+    scene
+}
+
+/// Empty border, what every demo and scene file starts from.
+pub fn bordered_scene() -> Scene {
+    let mut scene = Scene::default();
+    scene.create_3d_border(BORDER_RADIUS, 5, BORDER_CENTER);
+    scene
+}
@@ -0,0 +1,99 @@
+use crate::scene_file::SceneFileError;
+#[cfg(feature = "scripting")]
+use crate::scripting::ScriptError;
+use std::fmt;
+
+/// Everything that can stop the simulator from starting up or keep it from rendering.
+#[derive(Debug)]
+pub enum SimError {
+    EventLoop(winit::error::EventLoopError),
+    Window(winit::error::OsError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The adapter can't present to the window in any format.
+    UnsupportedSurface,
+    /// A shader failed to compile or a render pipeline couldn't be created for this adapter.
+    RenderSetup(String),
+    Surface(wgpu::SurfaceError),
+    SceneFile(SceneFileError),
+    #[cfg(feature = "scripting")]
+    Script(ScriptError),
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EventLoop(e) => write!(f, "unable to start the event loop: {e}"),
+            Self::Window(e) => write!(f, "unable to create a window: {e}"),
+            Self::CreateSurface(e) => write!(f, "unable to create a surface for the window: {e}"),
+            Self::NoAdapter => write!(f, "no graphics adapter found that is compatible with this window"),
+            Self::RequestDevice(e) => write!(f, "unable to open the graphics device: {e}"),
+            Self::UnsupportedSurface => write!(f, "the graphics adapter doesn't support any format for this window"),
+            Self::RenderSetup(e) => write!(f, "unable to set up rendering: {e}"),
+            Self::Surface(e) => write!(f, "unable to render to the window: {e}"),
+            Self::SceneFile(e) => write!(f, "unable to load the scene: {e}"),
+            #[cfg(feature = "scripting")]
+            Self::Script(e) => write!(f, "unable to run the script: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::EventLoop(e) => Some(e),
+            Self::Window(e) => Some(e),
+            Self::CreateSurface(e) => Some(e),
+            Self::RequestDevice(e) => Some(e),
+            Self::Surface(e) => Some(e),
+            Self::SceneFile(e) => Some(e),
+            #[cfg(feature = "scripting")]
+            Self::Script(e) => Some(e),
+            Self::NoAdapter | Self::UnsupportedSurface | Self::RenderSetup(_) => None,
+        }
+    }
+}
+
+impl From<winit::error::EventLoopError> for SimError {
+    fn from(e: winit::error::EventLoopError) -> Self {
+        Self::EventLoop(e)
+    }
+}
+
+impl From<winit::error::OsError> for SimError {
+    fn from(e: winit::error::OsError) -> Self {
+        Self::Window(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for SimError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        Self::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for SimError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for SimError {
+    fn from(e: wgpu::SurfaceError) -> Self {
+        Self::Surface(e)
+    }
+}
+
+impl From<SceneFileError> for SimError {
+    fn from(e: SceneFileError) -> Self {
+        Self::SceneFile(e)
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl From<ScriptError> for SimError {
+    fn from(e: ScriptError) -> Self {
+        Self::Script(e)
+    }
+}
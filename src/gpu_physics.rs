@@ -0,0 +1,536 @@
+use crate::physics::{Mesh, Scene, Vertex};
+use crate::rendering::srgb_to_linear;
+use crate::{BORDER_CENTER, BORDER_RADIUS};
+use glam::{Vec3, Vec4};
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 64;
+/// Bodies a grid cell can hold. Must match BODIES_PER_CELL in gpu_physics.wgsl.
+const BODIES_PER_CELL: u32 = 16;
+/// Caps the grid resolution so it still fits in a storage buffer however small the balls are.
+const MAX_CELLS_PER_AXIS: u32 = 96;
+const SOLVER_ITERATIONS: usize = 3;
+
+/// Ball as stored on the GPU. The same buffer is simulated by the compute shaders and drawn as instance data.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuBody {
+    pub pos: [f32; 3],
+    pub radius: f32,
+    pub velocity: [f32; 3],
+    pub inverse_mass: f32,
+    /// Linear, unlike the sRGB colors used everywhere else.
+    pub color: [f32; 4],
+}
+
+impl GpuBody {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = [
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x3,
+            offset: 0,
+            shader_location: 3,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32,
+            offset: 12,
+            shader_location: 4,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x4,
+            offset: 32,
+            shader_location: 5,
+        },
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    gravity: [f32; 3],
+    dt: f32,
+    grid_min: [f32; 3],
+    cell_size: f32,
+    grid_size: [u32; 3],
+    body_count: u32,
+    border_center: [f32; 3],
+    border_radius: f32,
+}
+
+impl Params {
+    /// Grid over the border with cells at least as wide as the largest ball, so touching balls are always in
+    /// neighbouring cells.
+    fn new(bodies: &[GpuBody]) -> Self {
+        let max_radius = bodies.iter().map(|b| b.radius).fold(f32::EPSILON, f32::max);
+        let extent = 2.0 * BORDER_RADIUS;
+        let cell_size = (2.0 * max_radius).max(extent / MAX_CELLS_PER_AXIS as f32);
+        let cells = ((extent / cell_size).ceil() as u32).max(1);
+        Self {
+            gravity: [0.0; 3],
+            dt: 0.0,
+            grid_min: (BORDER_CENTER - BORDER_RADIUS).to_array(),
+            cell_size,
+            grid_size: [cells; 3],
+            body_count: bodies.len() as u32,
+            border_center: BORDER_CENTER.to_array(),
+            border_radius: BORDER_RADIUS,
+        }
+    }
+
+    fn cell_count(&self) -> u32 {
+        self.grid_size.iter().product()
+    }
+}
+
+/// Physics for scenes too big for the CPU: integration under gravity, a uniform grid broadphase and ball contacts,
+/// all in compute shaders. Bodies never leave the GPU, the buffer the shaders write is drawn directly as instances
+/// of a unit sphere. Constraints, force fields and everything else `Scene::update_physics` does are not simulated.
+pub struct GpuPhysics {
+    params: Params,
+    params_buffer: wgpu::Buffer,
+    bodies: wgpu::Buffer,
+    next: wgpu::Buffer,
+    grid_overflows: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    integrate: wgpu::ComputePipeline,
+    clear_grid: wgpu::ComputePipeline,
+    fill_grid: wgpu::ComputePipeline,
+    collide: wgpu::ComputePipeline,
+    sphere_vertices: wgpu::Buffer,
+    sphere_indices: wgpu::Buffer,
+    sphere_index_count: u32,
+}
+
+impl GpuPhysics {
+    pub fn new(device: &wgpu::Device, bodies: &[GpuBody]) -> Self {
+        let params = Params::new(bodies);
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Physics Params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Buffers can't be empty, so an empty scene still gets room for one body
+        let contents = if bodies.is_empty() {
+            vec![bytemuck::Zeroable::zeroed()]
+        } else {
+            bodies.to_vec()
+        };
+        let bodies = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GPU Bodies"),
+            contents: bytemuck::cast_slice(&contents),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+        });
+        let next = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Bodies Next"),
+            size: bodies.size(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let cell_counts = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Grid Counts"),
+            size: params.cell_count() as wgpu::BufferAddress * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let cell_bodies = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Grid Bodies"),
+            size: (params.cell_count() * BODIES_PER_CELL) as wgpu::BufferAddress * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let grid_overflows = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU Grid Overflows"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GPU Physics Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+                storage_entry(3),
+                storage_entry(4),
+                storage_entry(5),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GPU Physics Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bodies.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: next.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: cell_counts.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: cell_bodies.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: grid_overflows.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("gpu_physics.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GPU Physics Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        let sphere = Mesh::sphere(1.0, 8, Vec3::ZERO, Vec4::ONE);
+        let sphere_vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Unit Sphere Vertex Buffer"),
+            contents: bytemuck::cast_slice::<Vertex, _>(&sphere.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let sphere_indices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Unit Sphere Index Buffer"),
+            contents: bytemuck::cast_slice(&sphere.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            params,
+            params_buffer,
+            bodies,
+            next,
+            grid_overflows,
+            bind_group,
+            integrate: pipeline("integrate"),
+            clear_grid: pipeline("clear_grid"),
+            fill_grid: pipeline("fill_grid"),
+            collide: pipeline("collide"),
+            sphere_vertices,
+            sphere_indices,
+            sphere_index_count: sphere.indices.len() as u32,
+        }
+    }
+
+    /// Uploads the balls of `scene`, drawn in their own colors.
+    pub fn from_scene(device: &wgpu::Device, scene: &Scene) -> Self {
+        let bodies = scene
+            .physics_bodies
+            .iter()
+            .zip(&scene.dynamic_meshes)
+            .map(|(b, mesh)| GpuBody {
+                pos: b.pos.to_array(),
+                radius: b.radius,
+                velocity: b.velocity.to_array(),
+                inverse_mass: b.inverse_mass(),
+                color: srgb_to_linear(mesh.color()).to_array(),
+            })
+            .collect::<Vec<_>>();
+        Self::new(device, &bodies)
+    }
+
+    pub fn body_count(&self) -> usize {
+        self.params.body_count as usize
+    }
+
+    /// Advances the simulation by `dt`.
+    pub fn step(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, gravity: Vec3, dt: f32) {
+        if self.params.body_count == 0 {
+            return;
+        }
+        self.params.gravity = gravity.to_array();
+        self.params.dt = dt;
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&self.params));
+
+        let body_groups = self.params.body_count.div_ceil(WORKGROUP_SIZE);
+        let cell_groups = self.params.cell_count().div_ceil(WORKGROUP_SIZE);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GPU Physics Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.set_pipeline(&self.integrate);
+            pass.dispatch_workgroups(body_groups, 1, 1);
+        }
+        // The grid is rebuilt from where the previous pass left the bodies, so ones it pushed into another cell are
+        // still found by the next
+        for _ in 0..SOLVER_ITERATIONS {
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_bind_group(0, &self.bind_group, &[]);
+                for (pipeline, groups) in [
+                    (&self.clear_grid, cell_groups),
+                    (&self.fill_grid, body_groups),
+                    (&self.collide, body_groups),
+                ] {
+                    pass.set_pipeline(pipeline);
+                    pass.dispatch_workgroups(groups, 1, 1);
+                }
+            }
+            encoder.copy_buffer_to_buffer(&self.next, 0, &self.bodies, 0, self.bodies.size());
+        }
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Draws every body as an instance of a unit sphere. Expects a pipeline using `vs_instanced` to be set.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_vertex_buffer(0, self.sphere_vertices.slice(..));
+        render_pass.set_vertex_buffer(1, self.bodies.slice(..));
+        render_pass.set_index_buffer(self.sphere_indices.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.sphere_index_count, 0, 0..self.params.body_count);
+    }
+
+    /// Copies the bodies back from the GPU, waiting for every step submitted so far to finish.
+    pub fn read_bodies(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<GpuBody> {
+        bytemuck::cast_slice::<_, GpuBody>(&read_buffer(device, queue, &self.bodies))[..self.body_count()].to_vec()
+    }
+
+    /// How many times a body was left out of the grid because its cell already held `BODIES_PER_CELL`, over every
+    /// step so far. Those bodies miss their contacts for that pass and can pass through each other, which the CPU
+    /// physics never does, so anything but 0 means the GPU results can no longer be trusted. Waits for every step
+    /// submitted so far to finish.
+    pub fn grid_overflows(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> u32 {
+        bytemuck::pod_read_unaligned(&read_buffer(device, queue, &self.grid_overflows))
+    }
+}
+
+/// Copies `buffer` back from the GPU, waiting for every submitted command to finish.
+fn read_buffer(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> Vec<u8> {
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("GPU Physics Readback"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+
+    let slice = staging.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| ());
+    device.poll(wgpu::Maintain::Wait);
+    let contents = slice.get_mapped_range().to_vec();
+    staging.unmap();
+    contents
+}
+
+/// CPU version of the compute shaders, following them step for step. Contacts are found by testing every pair
+/// instead of through the grid, so only rounding should tell the two apart.
+#[cfg(test)]
+fn reference_step(bodies: &mut [GpuBody], gravity: Vec3, dt: f32) {
+    const RESTITUTION: f32 = 0.95;
+    const BORDER_ELASTICITY: f32 = 0.95;
+
+    for b in bodies.iter_mut() {
+        let acceleration = if b.inverse_mass > 0.0 { gravity } else { Vec3::ZERO };
+        let velocity = Vec3::from(b.velocity) + acceleration * dt;
+        b.velocity = velocity.to_array();
+        b.pos = (Vec3::from(b.pos) + velocity * dt).to_array();
+    }
+
+    for _ in 0..SOLVER_ITERATIONS {
+        let previous = bodies.to_vec();
+        for (i, result) in bodies.iter_mut().enumerate() {
+            let body = previous[i];
+            let (pos, velocity) = (Vec3::from(body.pos), Vec3::from(body.velocity));
+            let mut pos_correction = Vec3::ZERO;
+            let mut velocity_correction = Vec3::ZERO;
+            let mut count = 0.0;
+            for (j, other) in previous.iter().enumerate().filter(|&(j, _)| j != i) {
+                let offset = Vec3::from(other.pos) - pos;
+                let distance = offset.length();
+                let inverse_mass = body.inverse_mass + other.inverse_mass;
+                if distance >= body.radius + other.radius || inverse_mass == 0.0 {
+                    continue;
+                }
+                let normal = if distance > 0.0 {
+                    offset / distance
+                } else if i < j {
+                    Vec3::Y
+                } else {
+                    Vec3::NEG_Y
+                };
+                let velocity_along_normal = (Vec3::from(other.velocity) - velocity).dot(normal);
+                if velocity_along_normal > 0.0 {
+                    continue;
+                }
+                let impulse = -(1.0 + RESTITUTION) * velocity_along_normal / inverse_mass;
+                velocity_correction -= normal * impulse * body.inverse_mass;
+                pos_correction -= normal * (body.radius + other.radius - distance) * body.inverse_mass / inverse_mass;
+                count += 1.0;
+            }
+
+            let (mut pos, mut velocity) = (pos, velocity);
+            if count > 0.0 {
+                pos += pos_correction / count;
+                velocity += velocity_correction / count;
+            }
+            let from_center = pos - BORDER_CENTER;
+            let distance_from_center = from_center.length();
+            if distance_from_center + body.radius > BORDER_RADIUS && body.inverse_mass > 0.0 {
+                let dir = from_center / distance_from_center;
+                pos = BORDER_CENTER + dir * (BORDER_RADIUS - body.radius);
+                velocity -= 2.0 * velocity.dot(dir) * dir;
+                velocity *= BORDER_ELASTICITY;
+            }
+            result.pos = pos.to_array();
+            result.velocity = velocity.to_array();
+        }
+    }
+}
+
+/// Device on the software adapter, or `None` if there isn't one that can run compute shaders.
+#[cfg(test)]
+pub(crate) fn software_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    }))?;
+    let downlevel = adapter.get_downlevel_capabilities();
+    if !downlevel.flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+        return None;
+    }
+    let descriptor = wgpu::DeviceDescriptor {
+        required_limits: adapter.limits(),
+        ..Default::default()
+    };
+    pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::GRAVITY;
+    use crate::DT;
+
+    /// A block of slightly overlapping balls flying in different directions, some of them into the border. One near
+    /// the middle is immovable.
+    fn bodies() -> Vec<GpuBody> {
+        let mut bodies = Vec::new();
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    let cell = Vec3::new(x as f32, y as f32, z as f32) - 1.5;
+                    bodies.push(GpuBody {
+                        pos: (BORDER_CENTER + cell * 0.095).to_array(),
+                        radius: 0.05,
+                        velocity: (Vec3::new(cell.y, -cell.z, cell.x) * 2.0).to_array(),
+                        inverse_mass: if (x, y, z) == (2, 2, 2) {
+                            0.0
+                        } else {
+                            1.0 / (1.0 + (x + y) as f32 * 0.5)
+                        },
+                        color: [1.0; 4],
+                    });
+                }
+            }
+        }
+        bodies
+    }
+
+    #[test]
+    fn gpu_matches_cpu_reference() {
+        let Some((device, queue)) = software_device() else {
+            eprintln!("skipping: no software adapter with compute shaders");
+            return;
+        };
+        let mut expected = bodies();
+        let mut gpu = GpuPhysics::new(&device, &expected);
+
+        for step in 0..50 {
+            gpu.step(&device, &queue, GRAVITY, DT);
+            reference_step(&mut expected, GRAVITY, DT);
+
+            let actual = gpu.read_bodies(&device, &queue);
+            for (i, (a, e)) in actual.iter().zip(&expected).enumerate() {
+                let pos_error = Vec3::from(a.pos).distance(Vec3::from(e.pos));
+                let velocity_error = Vec3::from(a.velocity).distance(Vec3::from(e.velocity));
+                assert!(
+                    pos_error < 1e-4 && velocity_error < 1e-2,
+                    "body {i} at step {step}: gpu {a:?}, cpu {e:?}"
+                );
+            }
+        }
+        assert_eq!(gpu.grid_overflows(&device, &queue), 0);
+    }
+
+    #[test]
+    fn full_cells_count_overflows() {
+        let Some((device, queue)) = software_device() else {
+            eprintln!("skipping: no software adapter with compute shaders");
+            return;
+        };
+        // Tiny balls in a clump at the center, more than fit in the one cell they share. They're immovable so contacts
+        // don't spread them into other cells between passes
+        let body_count = BODIES_PER_CELL + 8;
+        let bodies = (0..body_count)
+            .map(|i| GpuBody {
+                pos: (BORDER_CENTER + Vec3::X * i as f32 * 1e-4).to_array(),
+                radius: 1e-3,
+                velocity: [0.0; 3],
+                inverse_mass: 0.0,
+                color: [1.0; 4],
+            })
+            .collect::<Vec<_>>();
+        let mut gpu = GpuPhysics::new(&device, &bodies);
+        assert!(gpu.params.cell_size > 0.01, "every ball lands in the same cell");
+        assert_eq!(gpu.grid_overflows(&device, &queue), 0);
+
+        gpu.step(&device, &queue, Vec3::ZERO, DT);
+        let per_pass = body_count - BODIES_PER_CELL;
+        assert_eq!(gpu.grid_overflows(&device, &queue), per_pass * SOLVER_ITERATIONS as u32);
+    }
+}
@@ -0,0 +1,153 @@
+// Physics step for large numbers of balls, the GPU counterpart of `reference_step` in gpu_physics.rs. Each step runs
+// `integrate` once, then `clear_grid`, `fill_grid` and `collide` a few times with the output of `collide` copied back
+// to `bodies` in between.
+
+const WORKGROUP_SIZE: u32 = 64u;
+// Must match BODIES_PER_CELL in gpu_physics.rs
+const BODIES_PER_CELL: u32 = 16u;
+const RESTITUTION: f32 = 0.95;
+const BORDER_ELASTICITY: f32 = 0.95;
+
+struct Body {
+    pos: vec3<f32>,
+    radius: f32,
+    velocity: vec3<f32>,
+    inverse_mass: f32,
+    color: vec4<f32>,
+};
+
+struct Params {
+    gravity: vec3<f32>,
+    dt: f32,
+    grid_min: vec3<f32>,
+    cell_size: f32,
+    grid_size: vec3<u32>,
+    body_count: u32,
+    border_center: vec3<f32>,
+    border_radius: f32,
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read_write> bodies: array<Body>;
+@group(0) @binding(2) var<storage, read_write> next: array<Body>;
+@group(0) @binding(3) var<storage, read_write> cell_counts: array<atomic<u32>>;
+@group(0) @binding(4) var<storage, read_write> cell_bodies: array<u32>;
+// Bodies `fill_grid` had no room for, counted up over every step so far
+@group(0) @binding(5) var<storage, read_write> grid_overflows: atomic<u32>;
+
+fn cell_coords(pos: vec3<f32>) -> vec3<i32> {
+    let coords = vec3<i32>(floor((pos - params.grid_min) / params.cell_size));
+    return clamp(coords, vec3(0), vec3<i32>(params.grid_size) - 1);
+}
+
+fn cell_index(coords: vec3<i32>) -> u32 {
+    let c = vec3<u32>(coords);
+    return (c.z * params.grid_size.y + c.y) * params.grid_size.x + c.x;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn integrate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.body_count {
+        return;
+    }
+    var body = bodies[i];
+    // Immovable bodies, with no inverse mass, feel no gravity
+    body.velocity += params.gravity * params.dt * select(0.0, 1.0, body.inverse_mass > 0.0);
+    body.pos += body.velocity * params.dt;
+    bodies[i] = body;
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn clear_grid(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x < arrayLength(&cell_counts) {
+        atomicStore(&cell_counts[id.x], 0u);
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn fill_grid(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.body_count {
+        return;
+    }
+    let cell = cell_index(cell_coords(bodies[i].pos));
+    let slot = atomicAdd(&cell_counts[cell], 1u);
+    // A full cell drops the body, which then misses its contacts for this pass
+    if slot < BODIES_PER_CELL {
+        cell_bodies[cell * BODIES_PER_CELL + slot] = i;
+    } else {
+        atomicAdd(&grid_overflows, 1u);
+    }
+}
+
+// Solves every contact of one body against the positions from the previous pass and averages the corrections, so
+// bodies can be handled in any order. Mirrors `PhysicsBody::collide_with` as seen from one side of the pair.
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn collide(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.body_count {
+        return;
+    }
+    let body = bodies[i];
+    var pos_correction = vec3(0.0);
+    var velocity_correction = vec3(0.0);
+    var count = 0.0;
+
+    let center = cell_coords(body.pos);
+    for (var dz = -1; dz <= 1; dz++) {
+        for (var dy = -1; dy <= 1; dy++) {
+            for (var dx = -1; dx <= 1; dx++) {
+                let coords = center + vec3(dx, dy, dz);
+                if any(coords < vec3(0)) || any(coords >= vec3<i32>(params.grid_size)) {
+                    continue;
+                }
+                let cell = cell_index(coords);
+                let occupants = min(atomicLoad(&cell_counts[cell]), BODIES_PER_CELL);
+                for (var slot = 0u; slot < occupants; slot++) {
+                    let j = cell_bodies[cell * BODIES_PER_CELL + slot];
+                    if j == i {
+                        continue;
+                    }
+                    let other = bodies[j];
+                    let offset = other.pos - body.pos;
+                    let distance = length(offset);
+                    let inverse_mass = body.inverse_mass + other.inverse_mass;
+                    if distance >= body.radius + other.radius || inverse_mass == 0.0 {
+                        continue;
+                    }
+                    // Coincident centers get pushed apart along y, in opposite directions for each of the pair
+                    var normal = select(vec3(0.0, -1.0, 0.0), vec3(0.0, 1.0, 0.0), i < j);
+                    if distance > 0.0 {
+                        normal = offset / distance;
+                    }
+                    let velocity_along_normal = dot(other.velocity - body.velocity, normal);
+                    if velocity_along_normal > 0.0 {
+                        continue;
+                    }
+                    let impulse = -(1.0 + RESTITUTION) * velocity_along_normal / inverse_mass;
+                    velocity_correction -= normal * impulse * body.inverse_mass;
+                    pos_correction -= normal * (body.radius + other.radius - distance) * body.inverse_mass / inverse_mass;
+                    count += 1.0;
+                }
+            }
+        }
+    }
+
+    var result = body;
+    if count > 0.0 {
+        result.pos += pos_correction / count;
+        result.velocity += velocity_correction / count;
+    }
+
+    // Same as `PhysicsBody::keep_within_border`, which immovable bodies are left out of
+    let from_center = result.pos - params.border_center;
+    let distance_from_center = length(from_center);
+    if distance_from_center + result.radius > params.border_radius && result.inverse_mass > 0.0 {
+        let dir = from_center / distance_from_center;
+        result.pos = params.border_center + dir * (params.border_radius - result.radius);
+        result.velocity -= 2.0 * dot(result.velocity, dir) * dir;
+        result.velocity *= BORDER_ELASTICITY;
+    }
+    next[i] = result;
+}
@@ -0,0 +1,237 @@
+use crate::rendering::srgb_to_linear;
+use glam::{Vec2, Vec4};
+use std::collections::HashMap;
+use std::time::Duration;
+use wgpu::{include_wgsl, PipelineCompilationOptions};
+use winit::dpi::PhysicalSize;
+
+/// Size in pixels of a single font pixel.
+const SCALE: f32 = 2.;
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const ADVANCE: f32 = (GLYPH_WIDTH + 1) as f32 * SCALE;
+const LINE_HEIGHT: f32 = (GLYPH_HEIGHT + 3) as f32 * SCALE;
+const MARGIN: f32 = 8.;
+const PADDING: f32 = 6.;
+
+const TEXT_COLOR: Vec4 = Vec4::new(0.9, 0.9, 0.9, 1.);
+const PANEL_COLOR: Vec4 = Vec4::new(0., 0., 0., 0.5);
+
+/// Everything shown in the overlay, gathered once per frame.
+#[derive(Clone, Debug, Default)]
+pub struct HudStats {
+    pub fps: f64,
+    pub frame_time: Duration,
+    pub physics_time: Duration,
+    pub bodies: usize,
+    /// Left out while the GPU runs the physics, since it doesn't count contacts or add up energy.
+    pub contacts: Option<usize>,
+    pub energy: Option<f32>,
+}
+
+impl HudStats {
+    fn lines(&self) -> [String; 6] {
+        [
+            format!("FPS      {:.1}", self.fps),
+            format!("FRAME    {:.2} MS", self.frame_time.as_secs_f64() * 1000.),
+            format!("PHYSICS  {:.2} MS", self.physics_time.as_secs_f64() * 1000.),
+            format!("BODIES   {}", self.bodies),
+            self.contacts
+                .map_or("CONTACTS N/A".to_string(), |contacts| format!("CONTACTS {contacts}")),
+            self.energy
+                .map_or("ENERGY   N/A".to_string(), |energy| format!("ENERGY   {energy:.4} J")),
+        ]
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct HudVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl HudVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Text overlay drawn on top of the scene. Glyphs come from a built in bitmap font and every lit font pixel becomes
+/// a quad, which keeps the overlay free of textures and font loading.
+pub struct Hud {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    pub visible: bool,
+}
+
+impl Hud {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("hud.wgsl"));
+        let shader_constants = HashMap::from([(String::from("ENCODE_SRGB"), f64::from(u8::from(!format.is_srgb())))]);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HUD Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HUD Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[HudVertex::desc()],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions {
+                    constants: &shader_constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer: Self::create_vertex_buffer(device, 0),
+            vertex_count: 0,
+            visible: true,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, vertices: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HUD Vertex Buffer"),
+            size: (vertices.max(1) * std::mem::size_of::<HudVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Lays out `stats` for a surface of `size` pixels and uploads the result, growing the vertex buffer if needed.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, size: PhysicalSize<u32>, stats: &HudStats) {
+        let lines = stats.lines();
+        let widest = lines.iter().map(|l| l.len()).max().unwrap_or(0) as f32;
+        let screen = Vec2::new(size.width as f32, size.height as f32);
+
+        let mut vertices = Vec::new();
+        let panel_size = Vec2::new(widest * ADVANCE, lines.len() as f32 * LINE_HEIGHT) + 2. * PADDING;
+        push_quad(&mut vertices, screen, Vec2::splat(MARGIN), panel_size, PANEL_COLOR);
+
+        let origin = Vec2::splat(MARGIN + PADDING);
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let glyph_origin = origin + Vec2::new(column as f32 * ADVANCE, row as f32 * LINE_HEIGHT);
+                for (y, bits) in glyph(c).iter().enumerate() {
+                    for x in (0..GLYPH_WIDTH).filter(|x| bits & (1 << (GLYPH_WIDTH - 1 - x)) != 0) {
+                        let pixel = glyph_origin + Vec2::new(x as f32, y as f32) * SCALE;
+                        push_quad(&mut vertices, screen, pixel, Vec2::splat(SCALE), TEXT_COLOR);
+                    }
+                }
+            }
+        }
+
+        let needed = (vertices.len() * std::mem::size_of::<HudVertex>()) as wgpu::BufferAddress;
+        if needed > self.vertex_buffer.size() {
+            // Leave some headroom so values that grow a digit don't reallocate every frame
+            self.vertex_buffer = Self::create_vertex_buffer(device, vertices.len() * 2);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if !self.visible {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Appends two triangles covering the rectangle at `pos` (in pixels from the top left corner) of `size` pixels.
+fn push_quad(vertices: &mut Vec<HudVertex>, screen: Vec2, pos: Vec2, size: Vec2, color: Vec4) {
+    let to_ndc = |p: Vec2| [p.x / screen.x * 2. - 1., 1. - p.y / screen.y * 2.];
+    let color = srgb_to_linear(color).to_array();
+    let [x0, y0] = to_ndc(pos);
+    let [x1, y1] = to_ndc(pos + size);
+    for position in [[x0, y0], [x0, y1], [x1, y0], [x1, y0], [x0, y1], [x1, y1]] {
+        vertices.push(HudVertex { position, color });
+    }
+}
+
+/// Rows of a 5x7 glyph, most significant bit on the left. Lowercase letters share the uppercase glyphs and anything
+/// without a glyph is drawn as a space.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
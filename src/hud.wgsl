@@ -0,0 +1,36 @@
+// Set when the surface format isn't sRGB, in which case the hardware won't encode the output for us
+override ENCODE_SRGB: bool = false;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = model.color;
+    out.clip_position = vec4<f32>(model.position, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if ENCODE_SRGB {
+        return vec4(linear_to_srgb(in.color.rgb), in.color.a);
+    }
+    return in.color;
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3(0.0031308));
+}
@@ -0,0 +1,28 @@
+pub mod camera;
+pub mod demos;
+pub mod error;
+pub mod gpu_physics;
+pub mod hud;
+pub mod physics;
+pub mod rendering;
+pub mod scene_file;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod settings;
+
+use glam::{vec3, Vec3};
+use physics::Electrostatics;
+
+pub const DT: f32 = 1E-3;
+
+pub const BORDER_RADIUS: f32 = 0.85;
+pub const BORDER_CENTER: Vec3 = vec3(0., 0., 0.);
+
+pub const BALL_RADIUS: f32 = 0.04;
+pub const BALL_START: Vec3 = vec3(0., 0.75, 0.0);
+
+pub const COULOMB: Electrostatics = Electrostatics {
+    k: 1.,
+    cutoff: 0.5,
+    softening: BALL_RADIUS,
+};
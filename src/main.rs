@@ -1,83 +1,248 @@
-mod physics;
-mod rendering;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use glam::{vec3, Vec3, Vec4};
-use physics::{Scene, Vertex};
-use rendering::{render_objects, BufferManager};
+use glam::{Vec2, Vec3, Vec4};
+use silly_goose::camera::{Camera, CameraUniform};
+use silly_goose::demos;
+use silly_goose::error::SimError;
+use silly_goose::gpu_physics::{GpuBody, GpuPhysics};
+use silly_goose::hud::{Hud, HudStats};
+use silly_goose::physics::{
+    Falloff, ForceField, Grab, Gravitation, Mass, PointAttractor, Scene, Solver, TimeVarying, Uniform, Vertex, Vortex,
+    Xpbd, GRAVITY,
+};
+use silly_goose::rendering::{render_lines, render_objects, srgb_to_linear, BufferManager};
+use silly_goose::scene_file::{self, SceneFileError};
+#[cfg(feature = "scripting")]
+use silly_goose::scripting::Script;
+use silly_goose::settings::Settings;
+use silly_goose::{BALL_RADIUS, BORDER_CENTER, BORDER_RADIUS, DT};
+use std::f32::consts::{PI, TAU};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::time::{Duration, Instant};
-use wgpu::{include_wgsl, Color, PipelineCompilationOptions};
+use wgpu::{include_wgsl, util::DeviceExt, Color, PipelineCompilationOptions};
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowId},
 };
 
-pub const DT: f32 = 1E-3;
+/// Density of balls spawned by clicking, so one of `BALL_RADIUS` weighs a kilogram like the balls in the demos and
+/// bigger ones are heavier.
+const SPAWN_DENSITY: f32 = 1. / (4. / 3. * PI * BALL_RADIUS * BALL_RADIUS * BALL_RADIUS);
+
+/// Radius and color combinations cycled through with C for balls spawned by clicking.
+const SPAWN_PRESETS: [(f32, Vec4); 4] = [
+    (BALL_RADIUS, Vec4::new(1., 1., 0., 1.)),
+    (0.025, Vec4::new(0.2, 0.6, 1., 1.)),
+    (0.06, Vec4::new(1., 0.3, 0.3, 1.)),
+    (0.09, Vec4::new(0.3, 0.9, 0.4, 1.)),
+];
+
+/// Impulse at the center of a right click explosion, and how far away it still reaches.
+const EXPLOSION_STRENGTH: f32 = 3.;
+const EXPLOSION_RADIUS: f32 = 0.4;
+
+/// Number of force field setups cycled through with F, see `field_preset`.
+const FIELD_PRESETS: usize = 6;
+
+/// Mutual gravitation toggled with N. Strong enough to visibly pull a handful of unit mass balls together, with
+/// softening on the order of a ball radius.
+const N_BODY: Gravitation = Gravitation {
+    g: 0.05,
+    softening: BALL_RADIUS,
+    theta: 0.5,
+};
+
+/// Charges cycled through with Q for balls spawned by clicking. Balls this close together push each other apart
+/// with a few times the acceleration of gravity.
+const SPAWN_CHARGES: [f32; 3] = [0., 0.3, -0.3];
+
+/// Background color, in sRGB like every other color handed to the renderer.
+const CLEAR_COLOR: Vec4 = Vec4::new(0.13, 0.15, 0.18, 1.0);
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Most steps taken at once to catch up while hidden. Time owed beyond that is dropped, so a machine that can't keep up
+/// falls behind real time instead of spending ever longer catching up.
+const MAX_CATCH_UP_STEPS: u32 = 100;
+
+/// Counts the physics steps owed to wall clock time, so a hidden window keeps the simulation in real time instead of
+/// stepping on every pass of the event loop.
+#[derive(Default)]
+struct StepClock {
+    last: Option<Instant>,
+    owed: Duration,
+}
+
+impl StepClock {
+    /// Steps of `DT` due at `now` since the last call. The first call after `stop` only starts the clock.
+    fn steps_due(&mut self, now: Instant) -> u32 {
+        let step = Duration::from_secs_f32(DT);
+        if let Some(last) = self.last {
+            self.owed += now.saturating_duration_since(last);
+        }
+        self.last = Some(now);
+        let steps = (self.owed.as_nanos() / step.as_nanos()) as u32;
+        self.owed -= step * steps;
+        if steps > MAX_CATCH_UP_STEPS {
+            self.owed = Duration::ZERO;
+        }
+        steps.min(MAX_CATCH_UP_STEPS)
+    }
 
-pub const BORDER_RADIUS: f32 = 0.85;
-pub const BORDER_CENTER: Vec3 = vec3(0., 0., 0.);
+    /// When the next step falls due, if the clock is running.
+    fn next_step(&self) -> Option<Instant> {
+        self.last
+            .map(|last| last + Duration::from_secs_f32(DT).saturating_sub(self.owed))
+    }
 
-const BALL_RADIUS: f32 = 0.04;
-const BALL_START: Vec3 = vec3(0., 0.75, 0.0);
+    fn stop(&mut self) {
+        *self = Self::default();
+    }
+}
 
 struct State {
     window: Arc<Window>,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    size: winit::dpi::PhysicalSize<u32>,
+    size: PhysicalSize<u32>,
     surface: wgpu::Surface<'static>,
     surface_format: wgpu::TextureFormat,
+    present_mode: wgpu::PresentMode,
+    sample_count: u32,
+    /// Rendering is paused while the window has no area to draw into or is completely hidden.
+    minimized: bool,
+    occluded: bool,
+    simulate_while_minimized: bool,
+    /// Paces the steps taken while rendering is paused.
+    hidden_clock: StepClock,
+    /// Where the starting scene came from, reloaded with 0
+    scene_file: Option<PathBuf>,
+    /// Sets up the scene and runs before every step, given with `--script`.
+    #[cfg(feature = "scripting")]
+    script: Option<Script>,
+    /// Set once the script has ended the run.
+    finished: bool,
     render_pipeline: wgpu::RenderPipeline,
+    line_pipeline: wgpu::RenderPipeline,
+    /// Draws the balls of `gpu_physics` straight from its body buffer.
+    instanced_pipeline: wgpu::RenderPipeline,
+    /// Whether the adapter can run the compute shaders `gpu_physics` needs.
+    supports_compute: bool,
+
+    camera: Camera,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
 
     scene: Scene,
+    /// Last known cursor position in pixels, `None` while it's outside the window.
+    cursor: Option<Vec2>,
+    /// Offset from the grabbed point to the center of the body being dragged.
+    grab_offset: Vec3,
+    /// Index into `SPAWN_PRESETS` used for the next spawned ball.
+    spawn_preset: usize,
+    /// Index into `SPAWN_CHARGES` used for the next spawned ball.
+    spawn_charge: usize,
+    field_preset: usize,
+    /// While set, the balls are simulated and drawn on the GPU and `scene` is only used for the border.
+    gpu_physics: Option<GpuPhysics>,
 
     buffers: BufferManager,
+    hud: Hud,
 
-    depth_texture: wgpu::Texture,
     depth_view: wgpu::TextureView,
+    /// Multisampled color target that gets resolved into the swapchain texture. `None` when MSAA is disabled.
+    msaa_view: Option<wgpu::TextureView>,
 
     last_frame_time: Instant,
     frame_count: u32,
     last_fps_update: Instant,
-    current_fps: f64,
+    /// Frame and physics time summed since the last FPS update, averaged once a second like the FPS
+    frame_time_sum: Duration,
+    physics_time_sum: Duration,
+    stats: HudStats,
 }
 
 impl State {
-    async fn new(window: Arc<Window>) -> State {
+    async fn new(window: Arc<Window>, settings: &Settings) -> Result<State, SimError> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance.create_surface(window.clone())?;
         let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
             .await
-            .unwrap();
+            .ok_or(SimError::NoAdapter)?;
         let (device, queue) = adapter
             .request_device(
-                &wgpu::DeviceDescriptor::default(),
+                &wgpu::DeviceDescriptor {
+                    // Sample counts other than 1 and 4 are only usable with adapter specific format features
+                    required_features: adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                    ..Default::default()
+                },
                 None, // Trace path
             )
-            .await
-            .unwrap();
+            .await?;
 
         let size = window.inner_size();
 
-        let surface = instance.create_surface(window.clone()).unwrap();
         let cap = surface.get_capabilities(&adapter);
-        let surface_format = cap
-            .formats
-            .into_iter()
-            .find(|f| matches!(f, wgpu::TextureFormat::Rgba8Unorm))
-            .unwrap();
+        let surface_format = choose_surface_format(&cap.formats).ok_or(SimError::UnsupportedSurface)?;
+        let present_mode = choose_present_mode(&cap.present_modes, settings.vsync);
+        let sample_count = choose_sample_count(&adapter, surface_format, settings.msaa_samples);
 
+        // Shader compilation and pipeline errors, like a sample count or override constant the adapter rejects, would
+        // otherwise go to the uncaptured error handler, which panics
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
         let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
+        let hud = Hud::new(&device, surface_format);
+        // Without an sRGB surface the hardware won't gamma encode on write, so the fragment shader has to
+        let shader_constants = HashMap::from([(
+            String::from("ENCODE_SRGB"),
+            f64::from(u8::from(!surface_format.is_srgb())),
+        )]);
+
+        let camera = Camera::new(size);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::bytes_of(&CameraUniform::from(&camera)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Camera Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera Bind Group"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
 
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
+            bind_group_layouts: &[&camera_bind_group_layout],
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let pipeline_descriptor = wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
             layout: Some(&render_pipeline_layout),
             vertex: wgpu::VertexState {
@@ -105,7 +270,10 @@ impl State {
                     }),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
-                compilation_options: PipelineCompilationOptions::default(),
+                compilation_options: PipelineCompilationOptions {
+                    constants: &shader_constants,
+                    ..Default::default()
+                },
             }),
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
@@ -117,42 +285,73 @@ impl State {
                 conservative: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
+                format: DEPTH_FORMAT,
                 depth_write_enabled: true,
                 depth_compare: wgpu::CompareFunction::Less,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
             cache: None,
+        };
+        let render_pipeline = device.create_render_pipeline(&pipeline_descriptor);
+        // Constraints are drawn unlit, as plain line segments
+        let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            fragment: pipeline_descriptor
+                .fragment
+                .clone()
+                .map(|fragment| wgpu::FragmentState {
+                    entry_point: Some("fs_flat"),
+                    ..fragment
+                }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                cull_mode: None,
+                ..pipeline_descriptor.primitive
+            },
+            ..pipeline_descriptor.clone()
         });
-
-        let mut scene = Scene::default();
-
-        scene.create_3d_border(BORDER_RADIUS, 5, BORDER_CENTER);
-        scene.add_ball(BALL_RADIUS, BALL_START, Vec4::new(1., 1., 0., 1.));
-        scene.add_ball(BALL_RADIUS, Vec3::new(0., 0., 0.), Vec4::new(1., 0., 0., 1.));
-
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width: size.width,
-                height: size.height,
-                depth_or_array_layers: 1,
+        let instanced_buffers = [Vertex::desc(), GpuBody::desc()];
+        let instanced_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Instanced Pipeline"),
+            vertex: wgpu::VertexState {
+                entry_point: Some("vs_instanced"),
+                buffers: &instanced_buffers,
+                ..pipeline_descriptor.vertex.clone()
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
+            ..pipeline_descriptor.clone()
         });
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = create_depth_view(&device, size, sample_count);
+        let msaa_view = create_msaa_view(&device, size, surface_format, sample_count);
+        if let Some(e) = device.pop_error_scope().await {
+            return Err(SimError::RenderSetup(e.to_string()));
+        }
+        let supports_compute = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+
+        let mut scene = starting_scene(
+            settings.scene.as_deref(),
+            cfg!(feature = "scripting") && settings.script.is_some(),
+        )?;
+        #[cfg(feature = "scripting")]
+        let script = match &settings.script {
+            Some(path) => {
+                let mut script = Script::load(path)?;
+                script.setup(&mut scene)?;
+                Some(script)
+            }
+            None => None,
+        };
+        scene.non_finite_policy = settings.non_finite_policy;
+
         let buffers = BufferManager::new(&device, &scene);
 
         let state = State {
@@ -162,24 +361,52 @@ impl State {
             size,
             surface,
             surface_format,
+            present_mode,
+            sample_count,
+            minimized: size.width == 0 || size.height == 0,
+            occluded: false,
+            simulate_while_minimized: settings.simulate_while_minimized,
+            hidden_clock: StepClock::default(),
+            scene_file: settings.scene.clone(),
+            #[cfg(feature = "scripting")]
+            script,
+            finished: false,
             render_pipeline,
+            line_pipeline,
+            instanced_pipeline,
+            supports_compute,
+
+            camera,
+            camera_buffer,
+            camera_bind_group,
 
             scene,
+            cursor: None,
+            grab_offset: Vec3::ZERO,
+            spawn_preset: 0,
+            spawn_charge: 0,
+            field_preset: 0,
+            gpu_physics: None,
 
             buffers,
+            hud,
 
-            depth_texture,
             depth_view,
+            msaa_view,
 
             last_frame_time: Instant::now(),
             frame_count: 0,
             last_fps_update: Instant::now(),
-            current_fps: 0.0,
+            frame_time_sum: Duration::ZERO,
+            physics_time_sum: Duration::ZERO,
+            stats: HudStats::default(),
         };
 
-        state.configure_surface();
+        if !state.minimized {
+            state.configure_surface();
+        }
 
-        state
+        Ok(state)
     }
 
     fn get_window(&self) -> &Window {
@@ -190,86 +417,367 @@ impl State {
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: self.surface_format,
-            // Request compatibility with the sRGB-format texture view we‘re going to create later.
             view_formats: vec![self.surface_format],
             alpha_mode: wgpu::CompositeAlphaMode::PreMultiplied,
             width: self.size.width,
             height: self.size.height,
             desired_maximum_frame_latency: 2,
-            present_mode: wgpu::PresentMode::Mailbox,
+            present_mode: self.present_mode,
         };
         self.surface.configure(&self.device, &surface_config);
     }
 
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        // A zero sized surface can't be configured. Keep the old one and its attachments until the window comes back.
+        self.minimized = new_size.width == 0 || new_size.height == 0;
+        if self.minimized {
+            return;
+        }
         self.size = new_size;
 
         self.configure_surface();
 
-        self.depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width: self.size.width,
-                height: self.size.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
-        self.depth_view = self.depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.camera.resize(self.size);
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::bytes_of(&CameraUniform::from(&self.camera)),
+        );
+
+        self.depth_view = create_depth_view(&self.device, self.size, self.sample_count);
+        self.msaa_view = create_msaa_view(&self.device, self.size, self.surface_format, self.sample_count);
     }
 
+    /// Grabs the body under the cursor. Returns whether there was one.
+    fn start_drag(&mut self) -> bool {
+        let Some(cursor) = self.cursor else {
+            return false;
+        };
+        let (origin, dir) = self.camera.ray(cursor, self.size);
+        let Some((body, _)) = self.scene.raycast(origin, dir) else {
+            return false;
+        };
+        // Keep the grabbed point under the cursor instead of snapping the center to it
+        let center = self.scene.physics_bodies[body].pos;
+        self.grab_offset = center - self.camera.cursor_on_plane(cursor, self.size, center);
+        self.scene.grab = Some(Grab::new(body, center));
+        true
+    }
 
-    fn render(&mut self) {
+    /// Clicking a ball picks it up, clicking anywhere else drops a new one there.
+    fn grab_or_spawn(&mut self) {
+        // Balls on the GPU can't be picked, and spawning would add them to the scene that isn't being simulated
+        if self.gpu_physics.is_some() {
+            return;
+        }
+        if !self.start_drag() {
+            self.spawn_ball();
+        }
+    }
+
+    /// Where the cursor points in the plane through the middle of the border, if it's in the window.
+    fn cursor_in_scene(&self) -> Option<Vec3> {
+        self.cursor
+            .map(|cursor| self.camera.cursor_on_plane(cursor, self.size, BORDER_CENTER))
+    }
+
+    /// Adds a ball under the cursor, moved inwards if it would stick out of the border.
+    fn spawn_ball(&mut self) {
+        let Some(pos) = self.cursor_in_scene() else {
+            return;
+        };
+        let (radius, color) = SPAWN_PRESETS[self.spawn_preset];
+        let pos = BORDER_CENTER + (pos - BORDER_CENTER).clamp_length_max(BORDER_RADIUS - radius);
+        let ball = self
+            .scene
+            .add_ball_with_mass(radius, pos, color, Mass::Density(SPAWN_DENSITY));
+        let body = &mut self.scene.physics_bodies[ball];
+        body.charge = SPAWN_CHARGES[self.spawn_charge];
+        // Balls spawned by hand are the ones that get thrown around hard
+        body.ccd = true;
+    }
+
+    fn explode(&mut self) {
+        if self.gpu_physics.is_some() {
+            return;
+        }
+        if let Some(pos) = self.cursor_in_scene() {
+            self.scene.apply_explosion(pos, EXPLOSION_STRENGTH, EXPLOSION_RADIUS);
+        }
+    }
+
+    /// Swaps in a different scene. Buffers are sized for the scene they were created with, so they are recreated too.
+    fn load_scene(&mut self, scene: Scene) {
+        self.buffers = BufferManager::new(&self.device, &scene);
+        let non_finite_policy = self.scene.non_finite_policy;
+        self.scene = scene;
+        self.scene.non_finite_policy = non_finite_policy;
+        self.field_preset = 0;
+        self.gpu_physics = None;
+    }
+
+    /// Moves the balls of the current scene onto the GPU, or brings them back to carry on where the GPU left off.
+    fn toggle_gpu_physics(&mut self) {
+        if let Some(gpu_physics) = self.gpu_physics.take() {
+            let bodies = gpu_physics.read_bodies(&self.device, &self.queue);
+            for (b, gpu_body) in self.scene.physics_bodies.iter_mut().zip(bodies) {
+                b.pos = gpu_body.pos.into();
+                b.velocity = gpu_body.velocity.into();
+            }
+            self.scene.wake_all();
+            self.scene.update_dynamic_vertices();
+            self.buffers
+                .update_dynamic_buffers(&self.device, &self.queue, &self.scene);
+        } else if self.supports_compute {
+            self.gpu_physics = Some(GpuPhysics::from_scene(&self.device, &self.scene));
+        } else {
+            eprintln!("GPU physics needs compute shaders, which this adapter doesn't support");
+        }
+    }
+
+    /// Brings the balls back to the CPU once the GPU grid has had to leave any out, since they'd start passing through
+    /// each other from then on. Waits for the GPU, so it's only checked once a second.
+    fn check_gpu_grid(&mut self) {
+        let Some(gpu_physics) = &self.gpu_physics else {
+            return;
+        };
+        let overflows = gpu_physics.grid_overflows(&self.device, &self.queue);
+        if overflows > 0 {
+            eprintln!("GPU grid cells overflowed {overflows} times, carrying on with CPU physics");
+            self.toggle_gpu_physics();
+        }
+    }
+
+    fn key_pressed(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Digit1 => self.load_scene(demos::sandbox()),
+            KeyCode::Digit2 => self.load_scene(demos::newtons_cradle()),
+            KeyCode::Digit3 => self.load_scene(demos::chain()),
+            KeyCode::Digit4 => self.load_scene(demos::soft_body()),
+            KeyCode::Digit5 => {
+                self.load_scene(demos::ball_pit());
+                self.toggle_gpu_physics();
+            }
+            KeyCode::Digit6 => self.load_scene(demos::pegs()),
+            KeyCode::Digit7 => self.load_scene(demos::tumbler()),
+            // Picks up edits to the scene file without restarting
+            KeyCode::Digit0 => self.restart(),
+            KeyCode::KeyP => self.toggle_gpu_physics(),
+            KeyCode::F1 => self.hud.visible = !self.hud.visible,
+            KeyCode::KeyC => self.spawn_preset = (self.spawn_preset + 1) % SPAWN_PRESETS.len(),
+            KeyCode::KeyQ => self.spawn_charge = (self.spawn_charge + 1) % SPAWN_CHARGES.len(),
+            // Sleeping bodies don't notice forces changing, so everything is woken up when they do
+            KeyCode::KeyG => {
+                self.scene.gravity = -self.scene.gravity;
+                self.scene.wake_all();
+            }
+            // Bodies only attract each other, so uniform gravity is switched off while it's on
+            KeyCode::KeyN => {
+                if self.scene.gravitation.take().is_some() {
+                    self.scene.gravity = GRAVITY;
+                } else {
+                    self.scene.gravitation = Some(N_BODY);
+                    self.scene.gravity = Vec3::ZERO;
+                }
+                self.scene.wake_all();
+            }
+            KeyCode::KeyX => {
+                self.scene.solver = match self.scene.solver {
+                    Solver::Impulse => Solver::Xpbd(Xpbd::default()),
+                    Solver::Xpbd(_) => Solver::Impulse,
+                }
+            }
+            #[cfg(feature = "parallel")]
+            KeyCode::KeyJ => self.scene.contact_solver = self.scene.contact_solver.next(),
+            KeyCode::KeyF => {
+                self.field_preset = (self.field_preset + 1) % FIELD_PRESETS;
+                self.scene.force_fields = field_preset(self.field_preset);
+                self.scene.wake_all();
+            }
+            _ => (),
+        }
+    }
+
+    fn update_drag(&mut self) {
+        let (Some(cursor), Some(grab)) = (self.cursor, self.scene.grab.as_mut()) else {
+            return;
+        };
+        let center = self.scene.physics_bodies[grab.body].pos;
+        grab.target = self.camera.cursor_on_plane(cursor, self.size, center) + self.grab_offset;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.minimized || self.occluded
+    }
+
+    /// Advances the simulation in real time when rendering is paused, so the scene doesn't freeze while the window is
+    /// hidden, on the GPU if that's where it runs. Returns how the event loop should wait for the next step.
+    fn update_hidden(&mut self, now: Instant) -> ControlFlow {
+        if !self.is_paused() {
+            self.hidden_clock.stop();
+            return ControlFlow::Poll;
+        }
+        if !self.simulate_while_minimized {
+            return ControlFlow::Wait;
+        }
+        for _ in 0..self.hidden_clock.steps_due(now) {
+            match &mut self.gpu_physics {
+                Some(gpu_physics) => gpu_physics.step(&self.device, &self.queue, self.scene.gravity, DT),
+                None => self.update_physics(),
+            }
+        }
+        self.hidden_clock
+            .next_step()
+            .map_or(ControlFlow::Poll, ControlFlow::WaitUntil)
+    }
+
+    /// Runs the script, if there is one, then steps the scene.
+    fn update_physics(&mut self) {
+        #[cfg(feature = "scripting")]
+        self.run_script();
         self.scene.update_physics(DT);
-        self.scene.update_dynamic_vertices();
-        self.buffers.update_dynamic_buffers(&self.queue, &self.scene);
+    }
+
+    /// Starts over if the script was edited, then runs its step hook. A script that fails is reported and left out
+    /// until it's edited again.
+    #[cfg(feature = "scripting")]
+    fn run_script(&mut self) {
+        if self.script.as_mut().is_some_and(Script::changed) {
+            println!("The script changed; starting over");
+            self.restart();
+        }
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        match script.step(&mut self.scene, DT) {
+            Ok(std::ops::ControlFlow::Continue(())) => {}
+            Ok(std::ops::ControlFlow::Break(reason)) => {
+                println!("The script ended the run: {reason}");
+                self.finished = true;
+            }
+            Err(e) => eprintln!("{}: {e}", script.path().display()),
+        }
+    }
+
+    /// Starts over from the scene file and script given at startup, picking up any edits to either. Does nothing
+    /// without either of them.
+    fn restart(&mut self) {
+        #[cfg(feature = "scripting")]
+        let scripted = self.script.is_some();
+        #[cfg(not(feature = "scripting"))]
+        let scripted = false;
+        if self.scene_file.is_none() && !scripted {
+            return;
+        }
+
+        let scene = starting_scene(self.scene_file.as_deref(), scripted).map_err(SimError::from);
+        #[cfg(feature = "scripting")]
+        let scene = scene.and_then(|mut scene| {
+            if let Some(script) = &mut self.script {
+                script.reload()?;
+                script.setup(&mut scene)?;
+            }
+            Ok(scene)
+        });
+        match scene {
+            Ok(scene) => self.load_scene(scene),
+            Err(e) => eprintln!("unable to start over: {e}"),
+        }
+    }
+
+    /// Draws a frame. Only fails when the GPU has run out of memory, anything else about the surface is recoverable.
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        let physics_start = Instant::now();
+        if let Some(gpu_physics) = &mut self.gpu_physics {
+            // Only measures handing the work to the GPU, not the GPU running it
+            gpu_physics.step(&self.device, &self.queue, self.scene.gravity, DT);
+            self.physics_time_sum += physics_start.elapsed();
+        } else {
+            self.update_physics();
+            self.physics_time_sum += physics_start.elapsed();
+            self.scene.update_dynamic_vertices();
+            self.buffers
+                .update_dynamic_buffers(&self.device, &self.queue, &self.scene);
+            if self.scene.container.is_moving() {
+                self.buffers.update_static_buffers(&self.queue, &self.scene);
+            }
+        }
         // Update FPS calculation
         self.frame_count += 1;
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_fps_update);
 
+        // Track frame time
+        self.frame_time_sum += now.duration_since(self.last_frame_time);
+        self.last_frame_time = now;
+
         // Update FPS every second
         if elapsed >= Duration::from_secs(1) {
-            self.current_fps = self.frame_count as f64 / elapsed.as_secs_f64();
-            println!("FPS: {:.2}", self.current_fps);
+            self.stats.fps = self.frame_count as f64 / elapsed.as_secs_f64();
+            self.stats.frame_time = self.frame_time_sum / self.frame_count;
+            self.stats.physics_time = self.physics_time_sum / self.frame_count;
             self.frame_count = 0;
+            self.frame_time_sum = Duration::ZERO;
+            self.physics_time_sum = Duration::ZERO;
             self.last_fps_update = now;
+            self.check_gpu_grid();
         }
 
-        // Track frame time
-        self.last_frame_time = now;
+        self.stats.bodies = self
+            .gpu_physics
+            .as_ref()
+            .map_or(self.scene.physics_bodies.len(), GpuPhysics::body_count);
+        // The CPU scene stands still while the GPU runs the physics, so its numbers would be stale
+        let cpu_physics = self.gpu_physics.is_none();
+        self.stats.contacts = cpu_physics.then_some(self.scene.contacts);
+        self.stats.energy = cpu_physics.then(|| self.scene.total_energy());
+        if self.hud.visible {
+            self.hud.prepare(&self.device, &self.queue, self.size, &self.stats);
+        }
 
         // Create texture view
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .expect("failed to acquire next swapchain texture");
+        let surface_texture = match self.surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            // The surface no longer matches the window, reconfigure it and try again next frame
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.configure_surface();
+                return Ok(());
+            }
+            // The compositor didn't hand out a texture in time, drop this frame
+            Err(wgpu::SurfaceError::Timeout | wgpu::SurfaceError::Other) => return Ok(()),
+            Err(e @ wgpu::SurfaceError::OutOfMemory) => return Err(e),
+        };
         let texture_view = surface_texture.texture.create_view(&wgpu::TextureViewDescriptor {
             format: Some(self.surface_format),
             ..Default::default()
         });
 
         let mut encoder = self.device.create_command_encoder(&Default::default());
+        let clear_color = srgb_to_linear(CLEAR_COLOR);
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    resolve_target: None,
+                    view: self.msaa_view.as_ref().unwrap_or(&texture_view),
+                    resolve_target: self.msaa_view.as_ref().map(|_| &texture_view),
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(Color {
-                            r: 0.13,
-                            g: 0.15,
-                            b: 0.18,
-                            a: 1.0,
+                            r: clear_color.x as f64,
+                            g: clear_color.y as f64,
+                            b: clear_color.z as f64,
+                            a: clear_color.w as f64,
                         }),
-                        store: wgpu::StoreOp::Store,
+                        // The multisampled texture is only needed until it has been resolved
+                        store: if self.msaa_view.is_some() {
+                            wgpu::StoreOp::Discard
+                        } else {
+                            wgpu::StoreOp::Store
+                        },
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
@@ -285,6 +793,7 @@ impl State {
             });
 
             render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
 
             render_objects(
                 &mut render_pass,
@@ -293,64 +802,288 @@ impl State {
                 &self.scene.static_meshes,
             );
 
-            render_objects(
-                &mut render_pass,
-                &self.buffers.dynamic_vertex_buffer,
-                &self.buffers.dynamic_index_buffer,
-                &self.scene.dynamic_meshes,
-            );
+            if let Some(gpu_physics) = &self.gpu_physics {
+                render_pass.set_pipeline(&self.instanced_pipeline);
+                gpu_physics.draw(&mut render_pass);
+            } else {
+                render_objects(
+                    &mut render_pass,
+                    &self.buffers.dynamic_vertex_buffer,
+                    &self.buffers.dynamic_index_buffer,
+                    &self.scene.dynamic_meshes,
+                );
+
+                render_pass.set_pipeline(&self.line_pipeline);
+                render_lines(
+                    &mut render_pass,
+                    &self.buffers.line_vertex_buffer,
+                    self.buffers.line_vertex_count,
+                );
+            }
+        }
+
+        // The overlay goes on after MSAA has been resolved, straight onto the swapchain texture
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("HUD Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            self.hud.draw(&mut render_pass);
         }
 
         // Submit commands
         self.queue.submit([encoder.finish()]);
         self.window.pre_present_notify();
         surface_texture.present();
+
+        Ok(())
+    }
+}
+
+/// Force fields to demo, on top of gravity. Index 0 is none at all.
+fn field_preset(index: usize) -> Vec<Box<dyn ForceField>> {
+    let attractor = PointAttractor {
+        center: BORDER_CENTER,
+        strength: 2.,
+        falloff: Falloff::InverseSquare,
+        softening: 0.1,
+    };
+    match index {
+        1 => vec![Box::new(attractor)],
+        2 => vec![Box::new(PointAttractor {
+            strength: -4.,
+            falloff: Falloff::InverseLinear,
+            ..attractor
+        })],
+        3 => vec![Box::new(Vortex {
+            center: BORDER_CENTER,
+            axis: Vec3::Z,
+            strength: 20.,
+            radius: BORDER_RADIUS,
+            pull: 5.,
+        })],
+        // Gusts of wind blowing back and forth once a second
+        4 => vec![Box::new(TimeVarying::new(
+            Uniform {
+                acceleration: Vec3::new(15., 0., 0.),
+            },
+            |t| (t * TAU).sin(),
+        ))],
+        // Alternates between pulling everything in and pushing it out twice a second
+        5 => vec![Box::new(TimeVarying::new(
+            PointAttractor {
+                strength: 10.,
+                falloff: Falloff::Constant,
+                ..attractor
+            },
+            |t| (t * 2. * TAU).sin(),
+        ))],
+        _ => Vec::new(),
+    }
+}
+
+/// Prefers formats the hardware gamma encodes on write. Surfaces list their formats in order of preference, so the
+/// first one is the fallback when there is no sRGB format, and the shader encodes instead.
+fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+    formats
+        .iter()
+        .copied()
+        .find(wgpu::TextureFormat::is_srgb)
+        .or_else(|| formats.first().copied())
+}
+
+/// Picks the present mode from what the surface supports. Fifo is guaranteed to be available, so it is both the vsync
+/// choice and the fallback when no lower latency mode exists.
+fn choose_present_mode(supported: &[wgpu::PresentMode], vsync: bool) -> wgpu::PresentMode {
+    let preferred: &[wgpu::PresentMode] = if vsync {
+        &[wgpu::PresentMode::Fifo]
+    } else {
+        &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate]
+    };
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(wgpu::PresentMode::Fifo)
+}
+
+/// Returns the largest sample count no greater than `requested` that both the color and depth formats support.
+fn choose_sample_count(adapter: &wgpu::Adapter, format: wgpu::TextureFormat, requested: u32) -> u32 {
+    let color = adapter.get_texture_format_features(format).flags;
+    let depth = adapter.get_texture_format_features(DEPTH_FORMAT).flags;
+    [16, 8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .find(|&count| color.sample_count_supported(count) && depth.sample_count_supported(count))
+        .unwrap_or(1)
+}
+
+fn create_depth_view(device: &wgpu::Device, size: PhysicalSize<u32>, sample_count: u32) -> wgpu::TextureView {
+    let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    depth_texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_msaa_view(
+    device: &wgpu::Device,
+    size: PhysicalSize<u32>,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> Option<wgpu::TextureView> {
+    if sample_count == 1 {
+        return None;
     }
+    let msaa_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("MSAA Color Texture"),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    Some(msaa_texture.create_view(&wgpu::TextureViewDescriptor::default()))
 }
 
 #[derive(Default)]
 struct App {
+    settings: Settings,
     state: Option<State>,
+    /// Set when the app had to stop early, so `main` can report it once the event loop has exited.
+    error: Option<SimError>,
+}
+
+impl App {
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: SimError) {
+        self.error = Some(error);
+        event_loop.exit();
+    }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Create window object
-        let window = Arc::new(event_loop.create_window(Window::default_attributes()).unwrap());
+        let window = match event_loop.create_window(Window::default_attributes()) {
+            Ok(window) => Arc::new(window),
+            Err(e) => return self.fail(event_loop, e.into()),
+        };
 
-        let state = pollster::block_on(State::new(window.clone()));
-        self.state = Some(state);
+        match pollster::block_on(State::new(window.clone(), &self.settings)) {
+            Ok(state) => self.state = Some(state),
+            Err(e) => return self.fail(event_loop, e),
+        }
 
         window.request_redraw();
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        let state = self.state.as_mut().unwrap();
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
         match event {
             WindowEvent::CloseRequested => {
                 println!("The close button was pressed; stopping");
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
-                state.render();
+                if let Err(e) = state.render() {
+                    return self.fail(event_loop, e.into());
+                }
                 state.get_window().request_redraw();
             }
             WindowEvent::Resized(size) => {
                 state.resize(size);
             }
+            WindowEvent::Occluded(occluded) => {
+                state.occluded = occluded;
+                // The redraws driving rendering may have stopped while hidden
+                if !occluded {
+                    state.get_window().request_redraw();
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => state.key_pressed(key),
+            WindowEvent::CursorMoved { position, .. } => {
+                state.cursor = Some(Vec2::new(position.x as f32, position.y as f32));
+                state.update_drag();
+            }
+            WindowEvent::CursorLeft { .. } => {
+                state.cursor = None;
+            }
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => match (button, button_state) {
+                (MouseButton::Left, ElementState::Pressed) => state.grab_or_spawn(),
+                (MouseButton::Left, ElementState::Released) => state.scene.grab = None,
+                (MouseButton::Right, ElementState::Pressed) => state.explode(),
+                _ => (),
+            },
             _ => (),
         }
     }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        // Some platforms stop delivering redraws to hidden windows, so physics has to be driven from here instead
+        let control_flow = state.update_hidden(Instant::now());
+        event_loop.set_control_flow(control_flow);
+        if state.finished {
+            event_loop.exit();
+        }
+    }
 }
 
-fn main() {
-    // wgpu uses `log` for all of our logging, so we initialize a logger with the `env_logger` crate.
-    //
-    // To change the log level, set the `RUST_LOG` environment variable. See the `env_logger`
-    // documentation for more information.
-    env_logger::init();
+/// The scene from the scene file at `path` if there is one. Otherwise scripts start from an empty border, and
+/// everything else from the sandbox.
+fn starting_scene(path: Option<&Path>, scripted: bool) -> Result<Scene, SceneFileError> {
+    match path {
+        Some(path) => scene_file::load(path),
+        None if scripted => Ok(demos::bordered_scene()),
+        None => Ok(demos::sandbox()),
+    }
+}
 
-    let event_loop = EventLoop::new().unwrap();
+fn run(settings: Settings) -> Result<(), SimError> {
+    let event_loop = EventLoop::new()?;
 
     // When the current loop iteration finishes, immediately begin a new
     // iteration regardless of whether or not new events are available to
@@ -364,6 +1097,62 @@ fn main() {
     // the background.
     // event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = App::default();
-    event_loop.run_app(&mut app).unwrap();
+    let mut app = App {
+        settings,
+        ..Default::default()
+    };
+    event_loop.run_app(&mut app)?;
+
+    app.error.map_or(Ok(()), Err)
+}
+
+fn main() -> ExitCode {
+    // wgpu uses `log` for all of our logging, so we initialize a logger with the `env_logger` crate.
+    //
+    // To change the log level, set the `RUST_LOG` environment variable. See the `env_logger`
+    // documentation for more information.
+    env_logger::init();
+
+    match run(Settings::from_args(std::env::args().skip(1))) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_steps_follow_wall_clock() {
+        let step = Duration::from_secs_f32(DT);
+        let start = Instant::now();
+        let mut clock = StepClock::default();
+        assert_eq!(clock.steps_due(start), 0);
+
+        // Polled far more often than a step, the steps still add up to the time that passed
+        let mut steps = 0;
+        let mut now = start;
+        for _ in 0..1000 {
+            now += step / 7;
+            steps += clock.steps_due(now);
+            assert!(clock.next_step().unwrap() > now);
+        }
+        let expected = (now - start).as_secs_f64() / step.as_secs_f64();
+        assert!(
+            (steps as f64 - expected).abs() <= 1.0,
+            "{steps} steps in {expected} steps of time"
+        );
+
+        assert_eq!(clock.steps_due(now + step * 10), 10);
+        assert_eq!(
+            clock.steps_due(now + step * 10 + Duration::from_secs(60)),
+            MAX_CATCH_UP_STEPS
+        );
+        clock.stop();
+        assert_eq!(clock.steps_due(now + Duration::from_secs(120)), 0);
+    }
 }
@@ -1,13 +1,66 @@
-use crate::{BORDER_CENTER, BORDER_RADIUS};
+mod ccd;
+mod constraints;
+mod container;
+mod electrostatics;
+mod fields;
+mod grid;
+mod kinematic;
+mod nbody;
+mod parallel;
+mod sleep;
+mod soa;
+mod validation;
+mod xpbd;
+
+pub use constraints::{Anchor, Constraint, ConstraintKind};
+pub use container::{Container, Shake};
+pub use electrostatics::Electrostatics;
+pub use fields::{Falloff, ForceField, PointAttractor, TimeVarying, Uniform, Vortex};
+pub use kinematic::{Interpolation, Keyframe, Kinematic, MotionPath};
+pub use nbody::Gravitation;
+#[cfg(feature = "parallel")]
+pub use parallel::ContactSolver;
+pub use soa::BodyStore;
+pub use validation::{NonFiniteBody, NonFinitePolicy};
+pub use xpbd::Xpbd;
+
+use crate::rendering::srgb_to_linear;
+use crate::BORDER_RADIUS;
 use glam::{Vec3, Vec4};
+use grid::SpatialGrid;
+use parallel::{for_each_body, map_bodies};
 use std::f32::consts::PI;
 
+pub const GRAVITY: Vec3 = Vec3::new(0.0, -9.8, 0.0);
+
+/// Colors, in sRGB, for positively and negatively charged bodies when coloring by charge.
+const POSITIVE_CHARGE_COLOR: Vec4 = Vec4::new(1.0, 0.25, 0.2, 1.0);
+const NEGATIVE_CHARGE_COLOR: Vec4 = Vec4::new(0.2, 0.45, 1.0, 1.0);
+
+/// Sleeping bodies are drawn darker, with their color scaled by this.
+const SLEEPING_BRIGHTNESS: f32 = 0.4;
+
+/// Spring constant and damping, per unit mass, pulling a grabbed body towards its target. Damping is close to
+/// critical so the body follows the cursor without oscillating around it.
+const GRAB_STIFFNESS: f32 = 1000.0;
+const GRAB_DAMPING: f32 = 45.0;
+
 #[derive(Clone, Debug)]
 pub struct PhysicsBody {
     pub pos: Vec3,
     pub radius: f32,
     pub velocity: Vec3,
     pub mass: f32,
+    pub charge: f32,
+    /// Sleeping bodies are left out of integration and of contacts with each other until something wakes them.
+    pub asleep: bool,
+    /// How long the body has been moving slowly enough to fall asleep.
+    still_time: f32,
+    /// Sweep the body along its path every step so it can't pass through anything when moving fast.
+    pub ccd: bool,
+    /// Frozen after going non-finite. Quarantined bodies are asleep, never wake up and are immovable to everything
+    /// that hits them.
+    pub quarantined: bool,
 }
 
 impl PhysicsBody {
@@ -17,55 +70,204 @@ impl PhysicsBody {
             radius,
             velocity: Vec3::ZERO,
             mass: 1.0,
+            charge: 0.0,
+            asleep: false,
+            still_time: 0.0,
+            ccd: false,
+            quarantined: false,
         }
     }
 
+    /// Zero for immovable bodies, which have infinite mass, and for quarantined ones so contacts can't move them.
+    pub fn inverse_mass(&self) -> f32 {
+        if self.quarantined {
+            0.0
+        } else {
+            1.0 / self.mass
+        }
+    }
+
+    /// Whether nothing can push the body around. Immovable bodies feel no forces and aren't moved by contacts, but
+    /// still carry on at whatever velocity they're given.
+    pub fn is_immovable(&self) -> bool {
+        self.inverse_mass() == 0.0
+    }
+
+    /// Same as `keep_within` for a container that stays at `BORDER_CENTER`.
     pub fn keep_within_border(&mut self) {
-        let distance_from_center = self.pos.distance(BORDER_CENTER);
+        self.keep_within(&Container::default());
+    }
+
+    /// Moves the body back inside `container` if it got out, bouncing it off the wall.
+    pub fn keep_within(&mut self, container: &Container) {
+        let distance_from_center = self.pos.distance(container.center);
         if distance_from_center + self.radius > BORDER_RADIUS {
-            let dir = (self.pos - BORDER_CENTER).normalize();
-            self.pos = BORDER_CENTER + dir * (BORDER_RADIUS - self.radius);
+            // A body at the very center is only outside if it's bigger than the border, and gets pushed down
+            let dir = (self.pos - container.center).normalize_or(Vec3::NEG_Y);
+            self.pos = container.center + dir * (BORDER_RADIUS - self.radius);
 
+            // Bounced as seen from the wall where the body hit it, which moves with the container
+            let wall_velocity = container.wall_velocity(container.center + dir * BORDER_RADIUS);
+            let relative_velocity = self.velocity - wall_velocity;
             let normal = -dir;
-            let vel_along_normal = self.velocity.dot(normal);
-            self.velocity -= 2.0 * vel_along_normal * normal;
-            self.velocity *= 0.95; // Elasticity
+            let vel_along_normal = relative_velocity.dot(normal);
+            let sliding = relative_velocity - vel_along_normal * normal;
+            let bounced = -vel_along_normal * normal + sliding * (1.0 - container.friction);
+            self.velocity = wall_velocity + bounced * 0.95; // Elasticity
         }
     }
 
-    pub fn collide_with(&mut self, other: &mut PhysicsBody) {
+    /// Resolves an overlap between the two bodies, moving each in proportion to its inverse mass. Returns whether they
+    /// were touching.
+    pub fn collide_with(&mut self, other: &mut PhysicsBody) -> bool {
         let distance = self.pos.distance(other.pos);
 
         if distance < self.radius + other.radius {
-            let normal = (other.pos - self.pos).normalize();
+            let (inverse_mass_a, inverse_mass_b) = (self.inverse_mass(), other.inverse_mass());
+            let inverse_mass = inverse_mass_a + inverse_mass_b;
+            // Two immovable bodies just pass through each other
+            if inverse_mass == 0.0 {
+                return true;
+            }
+
+            // Bodies with the same center have no direction between them, so they're pushed apart vertically
+            let normal = (other.pos - self.pos).normalize_or(Vec3::Y);
 
             let relative_velocity = other.velocity - self.velocity;
 
             let velocity_along_normal = relative_velocity.dot(normal);
 
             if velocity_along_normal > 0.0 {
-                return;
+                return true;
             }
 
             let restitution = 0.95; // 95% elastic collision
             let mut impulse_scalar = -(1.0 + restitution) * velocity_along_normal;
-            impulse_scalar /= (1.0 / self.mass) + (1.0 / other.mass);
+            impulse_scalar /= inverse_mass;
 
             let impulse = impulse_scalar * normal;
-            self.velocity -= impulse / self.mass;
-            other.velocity += impulse / other.mass;
+            self.velocity -= impulse * inverse_mass_a;
+            other.velocity += impulse * inverse_mass_b;
 
             let overlap = (self.radius + other.radius) - distance;
-            let separation_vector = normal * (overlap * 0.5);
-            self.pos -= separation_vector;
-            other.pos += separation_vector;
+            let separation_vector = normal * (overlap / inverse_mass);
+            self.pos -= separation_vector * inverse_mass_a;
+            other.pos += separation_vector * inverse_mass_b;
+            return true;
+        }
+        false
+    }
+
+    /// Distance along the ray to where it first enters this body, if it hits at all. `dir` must be normalized.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3) -> Option<f32> {
+        let to_center = self.pos - origin;
+        let along = to_center.dot(dir);
+        let miss_distance_squared = to_center.length_squared() - along * along;
+        let half_chord_squared = self.radius * self.radius - miss_distance_squared;
+        if half_chord_squared < 0.0 {
+            return None;
+        }
+        let t = along - half_chord_squared.sqrt();
+        // A ray starting inside the body still counts as hitting it
+        (along + half_chord_squared.sqrt() >= 0.0).then_some(t.max(0.0))
+    }
+
+    /// Zero for immovable bodies, since no amount of energy could change how they move.
+    pub fn kinetic_energy(&self) -> f32 {
+        if self.is_immovable() {
+            return 0.0;
+        }
+        0.5 * self.mass * self.velocity.length_squared()
+    }
+}
+
+/// How heavy a ball added with `Scene::add_ball_with_mass` is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mass {
+    /// This many kilograms, whatever the ball's size.
+    Kilograms(f32),
+    /// Kilograms per cubic metre, so bigger balls are heavier.
+    Density(f32),
+    /// Can't be moved by forces or contacts, for fixed obstacles. It still moves at whatever velocity it's given.
+    Immovable,
+}
+
+impl Mass {
+    /// Mass in kilograms of a ball of `radius`.
+    pub fn of_ball(self, radius: f32) -> f32 {
+        match self {
+            Mass::Kilograms(mass) => mass,
+            Mass::Density(density) => density * 4.0 / 3.0 * PI * radius.powi(3),
+            Mass::Immovable => f32::INFINITY,
+        }
+    }
+}
+
+/// A body being dragged around. It's pulled towards `target` by a damped spring each step, so it still collides on
+/// the way and keeps whatever velocity it had when let go.
+#[derive(Clone, Debug)]
+pub struct Grab {
+    pub body: usize,
+    pub target: Vec3,
+    /// Target during the previous step, used to damp relative to the target's motion instead of to standing still.
+    previous_target: Vec3,
+}
+
+impl Grab {
+    pub fn new(body: usize, target: Vec3) -> Self {
+        Self {
+            body,
+            target,
+            previous_target: target,
         }
     }
 }
 
-#[derive(Clone, Default, Debug)]
+/// How a scene resolves contacts and constraints each step.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Solver {
+    /// Velocity impulses plus overlap corrections, repeated a few times per step.
+    #[default]
+    Impulse,
+    /// Extended position based dynamics with substeps. Stacks and chains settle instead of jittering.
+    Xpbd(Xpbd),
+}
+
+#[derive(Clone, Debug)]
 pub struct Scene {
     pub physics_bodies: Vec<PhysicsBody>,
+    pub grab: Option<Grab>,
+    /// Acceleration applied to every body, on top of whatever `force_fields` add.
+    pub gravity: Vec3,
+    pub force_fields: Vec<Box<dyn ForceField>>,
+    /// Mutual attraction between bodies, off unless set.
+    pub gravitation: Option<Gravitation>,
+    /// Forces between charged bodies, off unless set.
+    pub electrostatics: Option<Electrostatics>,
+    pub constraints: Vec<Constraint>,
+    /// Bodies moved along a path instead of simulated.
+    pub kinematic: Vec<Kinematic>,
+    /// The border, and how it moves.
+    pub container: Container,
+    pub solver: Solver,
+    /// How the impulse solver splits contacts across threads.
+    #[cfg(feature = "parallel")]
+    pub contact_solver: ContactSolver,
+    /// Let bodies that have come to rest fall asleep.
+    pub allow_sleep: bool,
+    /// Draw charged bodies in red or blue depending on the sign of their charge instead of their own color.
+    pub color_by_charge: bool,
+    /// Simulated seconds since the scene was created.
+    pub time: f32,
+    /// Physics steps taken since the scene was created.
+    pub steps: u64,
+    /// What to do with bodies that go non-finite.
+    pub non_finite_policy: NonFinitePolicy,
+    /// Bodies found non-finite after the last physics step, before `non_finite_policy` dealt with them, each with the
+    /// step it went bad on.
+    pub non_finite_bodies: Vec<NonFiniteBody>,
+    /// Number of touching pairs found during the last physics step.
+    pub contacts: usize,
 
     pub static_meshes: Vec<Mesh>,
     pub dynamic_meshes: Vec<Mesh>,
@@ -77,6 +279,38 @@ pub struct Scene {
     next_dynamic_index: usize,
 }
 
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            physics_bodies: Vec::new(),
+            grab: None,
+            gravity: GRAVITY,
+            force_fields: Vec::new(),
+            gravitation: None,
+            electrostatics: None,
+            constraints: Vec::new(),
+            kinematic: Vec::new(),
+            container: Container::default(),
+            solver: Solver::default(),
+            #[cfg(feature = "parallel")]
+            contact_solver: ContactSolver::default(),
+            allow_sleep: true,
+            color_by_charge: false,
+            time: 0.0,
+            steps: 0,
+            non_finite_policy: NonFinitePolicy::default(),
+            non_finite_bodies: Vec::new(),
+            contacts: 0,
+            static_meshes: Vec::new(),
+            dynamic_meshes: Vec::new(),
+            next_static_vertex: 0,
+            next_static_index: 0,
+            next_dynamic_vertex: 0,
+            next_dynamic_index: 0,
+        }
+    }
+}
+
 impl Scene {
     pub fn create_3d_border(&mut self, radius: f32, subdivisions: u32, center: Vec3) {
         let lat_steps = subdivisions;
@@ -110,7 +344,13 @@ impl Scene {
         }
     }
 
-    pub fn add_ball(&mut self, radius: f32, center: Vec3, color: Vec4) {
+    /// Adds a ball weighing one kilogram and returns its index into `physics_bodies`.
+    pub fn add_ball(&mut self, radius: f32, center: Vec3, color: Vec4) -> usize {
+        self.add_ball_with_mass(radius, center, color, Mass::Kilograms(1.0))
+    }
+
+    /// Adds a ball of the given `mass` and returns its index into `physics_bodies`.
+    pub fn add_ball_with_mass(&mut self, radius: f32, center: Vec3, color: Vec4, mass: Mass) -> usize {
         let mut mesh = Mesh::sphere(radius, 8, center, color);
 
         let vertex_offset = self.next_dynamic_vertex;
@@ -122,30 +362,202 @@ impl Scene {
 
         self.dynamic_meshes.push(mesh);
 
-        self.physics_bodies.push(PhysicsBody::new(center, radius));
+        self.physics_bodies.push(PhysicsBody {
+            mass: mass.of_ball(radius),
+            ..PhysicsBody::new(center, radius)
+        });
+        self.physics_bodies.len() - 1
     }
 
     pub fn update_physics(&mut self, dt: f32) {
-        self.physics_bodies.iter_mut().for_each(|b| {
-            let force = Vec3::new(0.0, -9.8 * b.mass, 0.0);
-            b.velocity += force * dt / b.mass;
+        self.wake_disturbed();
+        self.drive_kinematic(dt);
+        self.drive_container(dt);
+        let start = self.physics_bodies.iter().map(|b| b.pos).collect::<Vec<_>>();
+        let accelerations = self.accelerations(dt);
+        let touching = match self.solver.clone() {
+            Solver::Impulse => self.step_impulse(dt, &accelerations),
+            Solver::Xpbd(settings) => self.step_xpbd(dt, &accelerations, &settings),
+        };
+        self.validate(&start);
+        self.contacts = touching.len();
+        self.update_sleep(dt, &touching);
+        self.time += dt;
+        self.steps += 1;
+    }
+
+    /// Acceleration of every body from gravity, force fields, other bodies and the grab. Contacts and constraints are
+    /// left to the solver.
+    fn accelerations(&mut self, dt: f32) -> Vec<Vec3> {
+        let mut accelerations = map_bodies(&self.physics_bodies, |_, b| {
+            let field_force = self.force_fields.iter().map(|f| f.force(b, self.time)).sum::<Vec3>();
+            self.gravity + field_force / b.mass
+        });
+
+        if let Some(gravitation) = &self.gravitation {
+            for (total, acceleration) in accelerations
+                .iter_mut()
+                .zip(gravitation.accelerations(&self.physics_bodies))
+            {
+                *total += acceleration;
+            }
+        }
+
+        if let Some(electrostatics) = &self.electrostatics {
+            let forces = electrostatics.forces(&self.physics_bodies);
+            for ((total, force), b) in accelerations.iter_mut().zip(forces).zip(&self.physics_bodies) {
+                *total += force / b.mass;
+            }
+        }
+
+        if let Some(grab) = &mut self.grab {
+            let target_velocity = (grab.target - grab.previous_target) / dt;
+            grab.previous_target = grab.target;
+            let b = &self.physics_bodies[grab.body];
+            accelerations[grab.body] +=
+                GRAB_STIFFNESS * (grab.target - b.pos) + GRAB_DAMPING * (target_velocity - b.velocity);
+        }
+
+        // Forces on immovable bodies come out infinite or NaN, divided by their infinite mass
+        for (acceleration, b) in accelerations.iter_mut().zip(&self.physics_bodies) {
+            if b.is_immovable() {
+                *acceleration = Vec3::ZERO;
+            }
+        }
+        accelerations
+    }
+
+    /// Returns the pairs of bodies found touching.
+    fn step_impulse(&mut self, dt: f32, accelerations: &[Vec3]) -> Vec<(usize, usize)> {
+        for_each_body(&mut self.physics_bodies, |i, b| {
+            if !b.asleep {
+                b.velocity += accelerations[i] * dt;
+            }
+        });
+
+        for constraint in &self.constraints {
+            if constraint.is_asleep(&self.physics_bodies) {
+                continue;
+            }
+            constraint.apply_force(&mut self.physics_bodies, dt);
+        }
+
+        let start = self.physics_bodies.iter().map(|b| b.pos).collect::<Vec<_>>();
+        for_each_body(&mut self.physics_bodies, |_, b| {
+            if !b.asleep {
+                b.pos += b.velocity * dt;
+            }
         });
+        self.sweep_fast_bodies(&start);
 
-        self.physics_bodies.iter_mut().for_each(|b| b.pos += b.velocity * dt);
+        #[cfg(feature = "parallel")]
+        let margin = self.physics_bodies.iter().map(|b| b.radius).fold(0.0, f32::max) / 4.0;
 
         const SOLVER_ITERATIONS: usize = 3;
-        for _ in 0..SOLVER_ITERATIONS {
-            self.physics_bodies.iter_mut().for_each(PhysicsBody::keep_within_border);
-
-            for i in 0..self.physics_bodies.len() {
-                let (first, rest) = self.physics_bodies.split_at_mut(i);
-                for b1 in first {
-                    for b2 in rest.iter_mut() {
-                        b1.collide_with(b2);
-                    }
+        let mut touching = Vec::new();
+        for iteration in 0..SOLVER_ITERATIONS {
+            // Immovable bodies aren't pushed around by anything, the border included
+            for_each_body(&mut self.physics_bodies, |_, b| {
+                if !b.asleep && !b.is_immovable() {
+                    b.keep_within(&self.container);
                 }
+            });
+
+            for constraint in &self.constraints {
+                if constraint.is_asleep(&self.physics_bodies) {
+                    continue;
+                }
+                constraint.solve(&mut self.physics_bodies);
+            }
+
+            // Pairs are found again before every pass, the same as the serial solver checks every pair on every pass.
+            // The margin takes in bodies pushed into each other during the pass, and anything pushed further is
+            // caught on the next pass or step.
+            #[cfg(feature = "parallel")]
+            let touched = {
+                let pairs = self.nearby_pairs(margin);
+                self.collide_pairs_parallel(&pairs)
+            };
+            #[cfg(not(feature = "parallel"))]
+            let touched = self.collide_pairs();
+            // Later iterations only see what's left after the first pass pushed bodies apart
+            if iteration == 0 {
+                touching = touched;
             }
         }
+        touching
+    }
+
+    /// Resolves overlaps between every pair of bodies, in order, returning the pairs that were touching.
+    #[cfg(not(feature = "parallel"))]
+    fn collide_pairs(&mut self) -> Vec<(usize, usize)> {
+        let mut touching = Vec::new();
+        for i in 0..self.physics_bodies.len() {
+            let (first, rest) = self.physics_bodies.split_at_mut(i + 1);
+            let b1 = &mut first[i];
+            for (j, b2) in rest.iter_mut().enumerate() {
+                if b1.asleep && b2.asleep {
+                    continue;
+                }
+                if b1.collide_with(b2) {
+                    touching.push((i, i + 1 + j));
+                }
+            }
+        }
+        touching
+    }
+
+    /// Pairs of bodies less than `margin` apart, leaving out pairs that are both asleep.
+    fn nearby_pairs(&self, margin: f32) -> Vec<(usize, usize)> {
+        let Some(max_radius) = self.physics_bodies.iter().map(|b| b.radius).reduce(f32::max) else {
+            return Vec::new();
+        };
+        let grid = SpatialGrid::new(
+            2.0 * max_radius + margin,
+            self.physics_bodies.iter().enumerate().map(|(i, b)| (i, b.pos)),
+        );
+        grid.pairs(|a, b| {
+            let (body_a, body_b) = (&self.physics_bodies[a], &self.physics_bodies[b]);
+            !(body_a.asleep && body_b.asleep)
+                && body_a.pos.distance(body_b.pos) < body_a.radius + body_b.radius + margin
+        })
+    }
+
+    /// Returns the index of the first body hit by the ray and the distance to it. `dir` must be normalized.
+    pub fn raycast(&self, origin: Vec3, dir: Vec3) -> Option<(usize, f32)> {
+        self.physics_bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.raycast(origin, dir).map(|t| (i, t)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    /// Pushes every body within `radius` of `center` away from it. The impulse falls off linearly from `strength` at
+    /// the center to nothing at `radius`, and lighter bodies fly further.
+    pub fn apply_explosion(&mut self, center: Vec3, strength: f32, radius: f32) {
+        for b in &mut self.physics_bodies {
+            let offset = b.pos - center;
+            let distance = offset.length();
+            if distance < radius {
+                let falloff = 1.0 - distance / radius;
+                b.velocity += offset.normalize_or(Vec3::Y) * strength * falloff * b.inverse_mass();
+            }
+        }
+    }
+
+    /// Kinetic plus gravitational potential energy, with the bottom of the border (relative to gravity) where it is
+    /// now as zero height. Immovable bodies are left out, since their energy never changes.
+    pub fn total_energy(&self) -> f32 {
+        let up = -self.gravity.normalize_or_zero();
+        let floor = self.container.center.dot(up) - BORDER_RADIUS;
+        self.physics_bodies
+            .iter()
+            .filter(|b| !b.is_immovable())
+            .map(|b| {
+                let height = b.pos.dot(up) - floor;
+                b.kinetic_energy() + b.mass * self.gravity.length() * height
+            })
+            .sum()
     }
 
     pub fn update_dynamic_vertices(&mut self) {
@@ -159,11 +571,40 @@ impl Scene {
                 ];
             }
             mesh.center = body.pos;
+
+            let color = match body.charge {
+                q if self.color_by_charge && q > 0.0 => POSITIVE_CHARGE_COLOR,
+                q if self.color_by_charge && q < 0.0 => NEGATIVE_CHARGE_COLOR,
+                _ => mesh.color,
+            };
+            let color = if body.asleep {
+                (color.truncate() * SLEEPING_BRIGHTNESS).extend(color.w)
+            } else {
+                color
+            };
+            mesh.set_vertex_color(color);
         }
     }
 
+    /// Line list with a segment for every constraint.
+    pub fn constraint_vertices(&self) -> Vec<Vertex> {
+        self.constraints
+            .iter()
+            .flat_map(|c| c.line_vertices(&self.physics_bodies))
+            .collect()
+    }
+
+    /// Static meshes are fixed to the container, and kept where they'd be with it at rest. Their vertices are
+    /// returned where the container has taken them since.
     pub fn static_vertices(&self) -> Vec<Vertex> {
-        self.static_meshes.iter().flat_map(|m| m.vertices.clone()).collect()
+        let container = &self.container;
+        (self.static_meshes.iter().flat_map(|m| &m.vertices))
+            .map(|v| Vertex {
+                position: container.place(v.position.into()).to_array(),
+                normal: (container.rotation * Vec3::from(v.normal)).to_array(),
+                ..*v
+            })
+            .collect()
     }
 
     pub fn static_indices(&self) -> Vec<u32> {
@@ -185,9 +626,29 @@ pub struct Mesh {
     pub indices: Vec<u32>,
     pub buffer_offset: usize,
     center: Vec3,
+    /// Color the mesh was created with, in sRGB.
+    color: Vec4,
+    /// Color the vertices currently have, so they are only rewritten when it changes.
+    vertex_color: Vec4,
 }
 
 impl Mesh {
+    /// Color the mesh was created with, in sRGB.
+    pub fn color(&self) -> Vec4 {
+        self.color
+    }
+
+    /// Recolors every vertex, leaving the mesh's own color as is.
+    pub fn set_vertex_color(&mut self, color: Vec4) {
+        if color == self.vertex_color {
+            return;
+        }
+        for vertex in &mut self.vertices {
+            vertex.set_color(color);
+        }
+        self.vertex_color = color;
+    }
+
     pub fn sphere(radius: f32, num_subdivisions: u32, center: Vec3, color: Vec4) -> Self {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
@@ -236,10 +697,11 @@ impl Mesh {
             indices,
             buffer_offset: 0,
             center,
+            color,
+            vertex_color: color,
         }
     }
 
-    #[expect(unused)]
     pub fn polygon(radius: f32, num_subdivisions: u32, center: Vec3, color: Vec4, buffer_offset: usize) -> Self {
         let mut vertices = Vec::new();
         let angle_increment = (2. * PI) / num_subdivisions as f32;
@@ -263,6 +725,8 @@ impl Mesh {
             indices,
             buffer_offset,
             center,
+            color,
+            vertex_color: color,
         }
     }
 }
@@ -279,14 +743,20 @@ impl Vertex {
     const ATTRIBS: [wgpu::VertexAttribute; 3] =
         wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4, 2 => Float32x3];
 
+    /// `color` is given in sRGB and stored linear, so it looks the same whatever format the surface ends up using.
     pub fn new(position: Vec3, color: Vec4, normal: Vec3) -> Self {
         Self {
             position: position.to_array(),
-            color: color.to_array(),
+            color: srgb_to_linear(color).to_array(),
             normal: normal.to_array(),
         }
     }
 
+    /// Like in `new`, `color` is given in sRGB.
+    pub fn set_color(&mut self, color: Vec4) {
+        self.color = srgb_to_linear(color).to_array();
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
@@ -295,3 +765,164 @@ impl Vertex {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BORDER_CENTER;
+    use proptest::prelude::*;
+
+    fn vec3(range: f32) -> impl Strategy<Value = Vec3> {
+        (-range..range, -range..range, -range..range).prop_map(|(x, y, z)| Vec3::new(x, y, z))
+    }
+
+    fn body(pos: Vec3, radius: f32, velocity: Vec3, mass: f32) -> PhysicsBody {
+        PhysicsBody {
+            velocity,
+            mass,
+            ..PhysicsBody::new(pos, radius)
+        }
+    }
+
+    /// Two bodies of any size and mass, with the second somewhere around the first so they often overlap.
+    fn pair() -> impl Strategy<Value = (PhysicsBody, PhysicsBody)> {
+        (
+            vec3(1.0),
+            vec3(0.3),
+            0.01..0.2f32,
+            0.01..0.2f32,
+            vec3(10.0),
+            vec3(10.0),
+            0.1..10.0f32,
+            0.1..10.0f32,
+        )
+            .prop_map(
+                |(pos, offset, radius_a, radius_b, velocity_a, velocity_b, mass_a, mass_b)| {
+                    (
+                        body(pos, radius_a, velocity_a, mass_a),
+                        body(pos + offset, radius_b, velocity_b, mass_b),
+                    )
+                },
+            )
+    }
+
+    fn assert_finite(b: &PhysicsBody) {
+        assert!(b.pos.is_finite() && b.velocity.is_finite(), "{b:?}");
+    }
+
+    proptest! {
+        #[test]
+        fn momentum_is_conserved_with_equal_masses((mut a, mut b) in pair()) {
+            b.mass = a.mass;
+            let before = a.velocity + b.velocity;
+            a.collide_with(&mut b);
+            let after = a.velocity + b.velocity;
+            prop_assert!(after.distance(before) <= 1e-4 * (1.0 + before.length()), "{before} became {after}");
+        }
+
+        #[test]
+        fn kinetic_energy_never_increases((mut a, mut b) in pair()) {
+            let before = a.kinetic_energy() + b.kinetic_energy();
+            a.collide_with(&mut b);
+            let after = a.kinetic_energy() + b.kinetic_energy();
+            prop_assert!(after <= before * (1.0 + 1e-5) + 1e-6, "{before} became {after}");
+        }
+
+        #[test]
+        fn separated_pairs_are_untouched(
+            (mut a, mut b) in pair(),
+            direction in vec3(1.0).prop_filter("needs a direction", |d| d.length() > 1e-3),
+            gap in 1e-4..1.0f32,
+        ) {
+            b.pos = a.pos + direction.normalize() * (a.radius + b.radius + gap);
+            let (before_a, before_b) = (a.clone(), b.clone());
+            prop_assert!(!a.collide_with(&mut b));
+            prop_assert_eq!((a.pos, a.velocity), (before_a.pos, before_a.velocity));
+            prop_assert_eq!((b.pos, b.velocity), (before_b.pos, before_b.velocity));
+        }
+
+        #[test]
+        fn coincident_centers_are_pushed_apart((mut a, mut b) in pair()) {
+            // Bodies already moving apart are left to separate on their own, so these aren't
+            b.pos = a.pos;
+            b.velocity = a.velocity;
+            prop_assert!(a.collide_with(&mut b));
+            assert_finite(&a);
+            assert_finite(&b);
+            let distance = a.pos.distance(b.pos);
+            prop_assert!((distance - (a.radius + b.radius)).abs() < 1e-5, "{distance} apart");
+        }
+
+        #[test]
+        fn bodies_end_inside_border(pos in vec3(2.0), radius in 0.01..0.5f32, velocity in vec3(10.0)) {
+            let mut b = body(BORDER_CENTER + pos, radius, velocity, 1.0);
+            let energy = b.kinetic_energy();
+            b.keep_within_border();
+            assert_finite(&b);
+            prop_assert!(b.pos.distance(BORDER_CENTER) + b.radius <= BORDER_RADIUS + 1e-5);
+            prop_assert!(b.kinetic_energy() <= energy * (1.0 + 1e-5));
+        }
+    }
+
+    #[test]
+    fn mass_from_density_grows_with_volume() {
+        let small = Mass::Density(1000.0).of_ball(0.1);
+        assert!((small - 4.18879).abs() < 1e-4, "{small}");
+        assert!((Mass::Density(1000.0).of_ball(0.2) / small - 8.0).abs() < 1e-4);
+        assert_eq!(Mass::Kilograms(3.0).of_ball(0.2), 3.0);
+    }
+
+    #[test]
+    fn heavy_ball_barely_notices_light_one() {
+        let mut heavy = body(Vec3::ZERO, 0.1, Vec3::X, 100.0);
+        let mut light = body(Vec3::X * 0.15, 0.1, Vec3::ZERO, 1.0);
+        assert!(heavy.collide_with(&mut light));
+        assert!(heavy.velocity.x > 0.97, "{}", heavy.velocity);
+        assert!(light.velocity.x > 1.9, "{}", light.velocity);
+        // The overlap is taken up by the light ball
+        assert!(heavy.pos.x > -0.001 && light.pos.x > 0.199);
+    }
+
+    #[test]
+    fn immovable_bodies_stay_put() {
+        for solver in [Solver::Impulse, Solver::Xpbd(Xpbd::default())] {
+            let mut scene = Scene {
+                solver,
+                gravitation: Some(Gravitation {
+                    g: 0.05,
+                    softening: 0.04,
+                    theta: 0.5,
+                }),
+                ..Scene::default()
+            };
+            let peg = scene.add_ball_with_mass(0.1, BORDER_CENTER, Vec4::ONE, Mass::Immovable);
+            let ball = scene.add_ball(0.05, BORDER_CENTER + Vec3::new(0.02, 0.3, 0.0), Vec4::ONE);
+            scene.physics_bodies[ball].velocity = Vec3::NEG_Y * 2.0;
+            scene.apply_explosion(BORDER_CENTER, 1.0, 0.15);
+
+            for _ in 0..1000 {
+                scene.update_physics(crate::DT);
+            }
+            let (peg, ball) = (&scene.physics_bodies[peg], &scene.physics_bodies[ball]);
+            assert_eq!(
+                (peg.pos, peg.velocity),
+                (BORDER_CENTER, Vec3::ZERO),
+                "{:?}",
+                scene.solver
+            );
+            assert!(
+                ball.pos.is_finite() && ball.pos.y < -0.1,
+                "ball at {} didn't fall past",
+                ball.pos
+            );
+            assert!(scene.total_energy().is_finite());
+        }
+    }
+
+    #[test]
+    fn body_bigger_than_border_at_its_center_stays_finite() {
+        let mut b = body(BORDER_CENTER, BORDER_RADIUS * 2.0, Vec3::ONE, 1.0);
+        b.keep_within_border();
+        assert_finite(&b);
+    }
+}
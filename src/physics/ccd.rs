@@ -0,0 +1,209 @@
+use super::Scene;
+use crate::BORDER_RADIUS;
+use glam::Vec3;
+
+/// How far past the point of impact a swept body is left, so the discrete contact tests still see the overlap and
+/// respond to it.
+const SKIN: f32 = 1e-4;
+
+/// Earliest fraction of `motion` at which a sphere starting `offset` away from another, moving by `motion` relative
+/// to it, comes within `radius` of its center. `None` if they are already overlapping, moving apart or never meet
+/// during the move.
+fn sphere_time_of_impact(offset: Vec3, motion: Vec3, radius: f32) -> Option<f32> {
+    let approach = offset.dot(motion);
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 || approach >= 0.0 {
+        return None;
+    }
+    let a = motion.length_squared();
+    let discriminant = approach * approach - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = (-approach - discriminant.sqrt()) / a;
+    (t <= 1.0).then_some(t)
+}
+
+/// Earliest fraction of `motion` at which a sphere of `radius` starting at `start` touches the inside of the border
+/// around `center`. `None` if it is already outside, which the discrete border test handles, or stays inside for the
+/// whole move.
+fn border_time_of_impact(center: Vec3, start: Vec3, motion: Vec3, radius: f32) -> Option<f32> {
+    let offset = start - center;
+    let reach = BORDER_RADIUS - radius;
+    let c = offset.length_squared() - reach * reach;
+    let a = motion.length_squared();
+    if c >= 0.0 || a == 0.0 {
+        return None;
+    }
+    let b = offset.dot(motion);
+    // Starting inside, there is always exactly one crossing ahead
+    let t = (-b + (b * b - a * c).sqrt()) / a;
+    (t <= 1.0).then_some(t)
+}
+
+impl Scene {
+    /// Swept sphere continuous collision detection. Every awake body with `ccd` set is checked along the straight
+    /// line it just moved from `start`, against the border and against the other bodies moving along theirs. Bodies
+    /// that would have hit something are pulled back to just past the first impact, both of them for an impact
+    /// between two bodies, so the contact is resolved where it happened instead of them passing through each other.
+    /// Only one of the two needs `ccd` set.
+    pub(super) fn sweep_fast_bodies(&mut self, start: &[Vec3]) {
+        let bodies = &self.physics_bodies;
+        let motions = bodies.iter().zip(start).map(|(b, &s)| b.pos - s).collect::<Vec<_>>();
+        // Fraction of its motion each body gets to keep
+        let mut impacts = vec![1.0f32; bodies.len()];
+        for (i, body) in bodies.iter().enumerate() {
+            if !body.ccd || body.asleep {
+                continue;
+            }
+            let distance = motions[i].length();
+            if let Some(t) = border_time_of_impact(self.container.center, start[i], motions[i], body.radius) {
+                impacts[i] = impacts[i].min(t + SKIN / distance);
+            }
+
+            for (j, other) in bodies.iter().enumerate() {
+                // Pairs where both sweep are only checked once
+                if j == i || (other.ccd && !other.asleep && j < i) {
+                    continue;
+                }
+                let reach = body.radius + other.radius;
+                // Too far apart to meet this step
+                if (start[i] - start[j]).length() > reach + distance + motions[j].length() {
+                    continue;
+                }
+                let relative_motion = motions[i] - motions[j];
+                if let Some(t) = sphere_time_of_impact(start[i] - start[j], relative_motion, reach) {
+                    let t = t + SKIN / relative_motion.length();
+                    impacts[i] = impacts[i].min(t);
+                    impacts[j] = impacts[j].min(t);
+                }
+            }
+        }
+
+        for (i, b) in self.physics_bodies.iter_mut().enumerate() {
+            if impacts[i] < 1.0 {
+                b.pos = start[i] + motions[i] * impacts[i];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{Solver, Xpbd};
+    use crate::{BORDER_CENTER, DT};
+    use glam::Vec4;
+
+    const SPEED: f32 = 100.0;
+    const RADIUS: f32 = 0.01;
+
+    fn fire(solver: Solver, dir: Vec3) {
+        let mut scene = Scene {
+            solver,
+            ..Scene::default()
+        };
+        let ball = scene.add_ball(RADIUS, BORDER_CENTER, Vec4::ONE);
+        scene.physics_bodies[ball].ccd = true;
+        scene.physics_bodies[ball].velocity = dir.normalize() * SPEED;
+
+        for step in 0..(1.0 / DT) as usize {
+            scene.update_physics(DT);
+            let b = &scene.physics_bodies[ball];
+            let distance = b.pos.distance(BORDER_CENTER);
+            assert!(
+                distance + b.radius <= BORDER_RADIUS + SKIN,
+                "escaped to {distance} at step {step}"
+            );
+        }
+    }
+
+    #[test]
+    fn fast_ball_stays_inside_border() {
+        for dir in [
+            Vec3::X,
+            Vec3::NEG_Y,
+            Vec3::new(1.0, 2.0, -0.5),
+            Vec3::new(-0.3, 0.1, 0.9),
+        ] {
+            fire(Solver::Impulse, dir);
+            fire(Solver::Xpbd(Xpbd::default()), dir);
+        }
+    }
+
+    /// Fires a small ball at another one sitting in its path and returns whether it hit instead of passing through.
+    fn hits_target(ccd: bool) -> bool {
+        let mut scene = Scene {
+            gravity: Vec3::ZERO,
+            ..Scene::default()
+        };
+        let bullet = scene.add_ball(RADIUS, BORDER_CENTER - Vec3::X * 0.5, Vec4::ONE);
+        let target = scene.add_ball(RADIUS, BORDER_CENTER + Vec3::X * 0.03, Vec4::ONE);
+        scene.physics_bodies[bullet].ccd = ccd;
+        scene.physics_bodies[bullet].velocity = Vec3::X * SPEED;
+
+        for _ in 0..10 {
+            scene.update_physics(DT);
+        }
+        scene.physics_bodies[target].velocity.x > 0.0
+    }
+
+    #[test]
+    fn fast_ball_hits_ball_in_its_path() {
+        assert!(
+            !hits_target(false),
+            "should tunnel without ccd, or the test isn't testing anything"
+        );
+        assert!(hits_target(true));
+    }
+
+    #[test]
+    fn fast_balls_meeting_head_on_bounce() {
+        // Only one of them needs `ccd` for the two to meet where they actually touch
+        let setups = [[true, false], [false, true], [true, true]]
+            .into_iter()
+            .flat_map(|ccd| [(ccd, Solver::Impulse), (ccd, Solver::Xpbd(Xpbd::default()))]);
+        for (ccd, solver) in setups {
+            let mut scene = Scene {
+                gravity: Vec3::ZERO,
+                solver,
+                ..Scene::default()
+            };
+            let left = scene.add_ball(RADIUS, BORDER_CENTER - Vec3::X * 0.35, Vec4::ONE);
+            let right = scene.add_ball(RADIUS, BORDER_CENTER + Vec3::X * 0.35, Vec4::ONE);
+            for (ball, ccd, dir) in [(left, ccd[0], 1.0), (right, ccd[1], -1.0)] {
+                scene.physics_bodies[ball].ccd = ccd;
+                scene.physics_bodies[ball].velocity = Vec3::X * dir * SPEED;
+            }
+
+            for step in 0..5 {
+                scene.update_physics(DT);
+                let (a, b) = (&scene.physics_bodies[left], &scene.physics_bodies[right]);
+                assert!(a.pos.x < b.pos.x, "{ccd:?}: passed through each other at step {step}");
+            }
+            assert!(scene.physics_bodies[left].velocity.x < 0.0, "{ccd:?}: never bounced");
+            assert!(scene.physics_bodies[right].velocity.x > 0.0, "{ccd:?}: never bounced");
+        }
+    }
+
+    #[test]
+    fn time_of_impact() {
+        let t = sphere_time_of_impact(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), 0.5).unwrap();
+        assert!((t - 0.25).abs() < 1e-6);
+        assert_eq!(
+            sphere_time_of_impact(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), 0.5),
+            None
+        );
+        assert_eq!(
+            sphere_time_of_impact(Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0), 0.5),
+            None
+        );
+
+        let t = border_time_of_impact(BORDER_CENTER, BORDER_CENTER, Vec3::X * BORDER_RADIUS * 2.0, 0.0).unwrap();
+        assert!((t - 0.5).abs() < 1e-6);
+        assert_eq!(
+            border_time_of_impact(BORDER_CENTER, BORDER_CENTER, Vec3::X * 0.1, 0.0),
+            None
+        );
+    }
+}
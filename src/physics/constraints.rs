@@ -0,0 +1,186 @@
+use super::{PhysicsBody, Vertex};
+use glam::{Vec3, Vec4};
+
+/// Line colors, in sRGB, for each kind of constraint.
+const DISTANCE_COLOR: Vec4 = Vec4::new(0.85, 0.85, 0.85, 1.0);
+const SPRING_COLOR: Vec4 = Vec4::new(0.4, 0.9, 0.5, 1.0);
+const ROPE_COLOR: Vec4 = Vec4::new(0.8, 0.6, 0.35, 1.0);
+
+/// One end of a constraint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    /// The center of the body at this index.
+    Body(usize),
+    /// A point that never moves, e.g. the pivot of a pendulum.
+    Fixed(Vec3),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConstraintKind {
+    /// Keeps the ends exactly `length` apart, like a massless rod.
+    Distance { length: f32 },
+    /// Hooke spring pulling the ends towards `rest_length`. `damping` resists the ends moving apart or together.
+    Spring {
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+    /// Stops the ends from getting further than `length` apart, but lets them get as close as they like.
+    Rope { length: f32 },
+}
+
+/// Connection between two bodies, or a body and a fixed point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub a: Anchor,
+    pub b: Anchor,
+    pub kind: ConstraintKind,
+}
+
+/// Position, velocity and inverse mass of an anchor. Fixed anchors can't be moved, so they have no inverse mass.
+fn state(anchor: Anchor, bodies: &[PhysicsBody]) -> (Vec3, Vec3, f32) {
+    match anchor {
+        Anchor::Body(i) => (bodies[i].pos, bodies[i].velocity, bodies[i].inverse_mass()),
+        Anchor::Fixed(pos) => (pos, Vec3::ZERO, 0.0),
+    }
+}
+
+fn nudge(anchor: Anchor, bodies: &mut [PhysicsBody], pos: Vec3, velocity: Vec3) {
+    if let Anchor::Body(i) = anchor {
+        bodies[i].pos += pos;
+        bodies[i].velocity += velocity;
+    }
+}
+
+impl Constraint {
+    pub fn new(a: Anchor, b: Anchor, kind: ConstraintKind) -> Self {
+        Self { a, b, kind }
+    }
+
+    /// Indices of the bodies at either end. Fixed anchors are left out.
+    pub fn bodies(&self) -> impl Iterator<Item = usize> {
+        [self.a, self.b].into_iter().filter_map(|anchor| match anchor {
+            Anchor::Body(i) => Some(i),
+            Anchor::Fixed(_) => None,
+        })
+    }
+
+    /// Whether every body attached to this constraint is asleep, leaving it nothing to do.
+    pub fn is_asleep(&self, bodies: &[PhysicsBody]) -> bool {
+        self.bodies().all(|i| bodies[i].asleep)
+    }
+
+    /// Applies the spring force over `dt`. Rigid constraints have no force and are handled by `solve` instead.
+    pub fn apply_force(&self, bodies: &mut [PhysicsBody], dt: f32) {
+        let ConstraintKind::Spring {
+            rest_length,
+            stiffness,
+            damping,
+        } = self.kind
+        else {
+            return;
+        };
+        let (pos_a, vel_a, inv_mass_a) = state(self.a, bodies);
+        let (pos_b, vel_b, inv_mass_b) = state(self.b, bodies);
+        let offset = pos_b - pos_a;
+        let dir = offset.normalize_or(Vec3::Y);
+        let stretch = offset.length() - rest_length;
+        let closing_speed = (vel_b - vel_a).dot(dir);
+
+        // Force on `a`, towards `b` while stretched
+        let force = (stiffness * stretch + damping * closing_speed) * dir;
+        nudge(self.a, bodies, Vec3::ZERO, force * inv_mass_a * dt);
+        nudge(self.b, bodies, Vec3::ZERO, -force * inv_mass_b * dt);
+    }
+
+    /// Moves the ends back to a valid distance and removes the velocity that would pull them apart again. Each end
+    /// moves in proportion to its inverse mass, so a light ball hanging from a fixed point does all the moving.
+    pub fn solve(&self, bodies: &mut [PhysicsBody]) {
+        let (length, is_rope) = match self.kind {
+            ConstraintKind::Distance { length } => (length, false),
+            ConstraintKind::Rope { length } => (length, true),
+            ConstraintKind::Spring { .. } => return,
+        };
+        let (pos_a, vel_a, inv_mass_a) = state(self.a, bodies);
+        let (pos_b, vel_b, inv_mass_b) = state(self.b, bodies);
+        let inv_mass = inv_mass_a + inv_mass_b;
+        if inv_mass == 0.0 {
+            return;
+        }
+
+        let offset = pos_b - pos_a;
+        let dir = offset.normalize_or(Vec3::Y);
+        let stretch = offset.length() - length;
+        // A slack rope doesn't do anything
+        if is_rope && stretch <= 0.0 {
+            return;
+        }
+
+        let separating_speed = (vel_b - vel_a).dot(dir);
+        let speed_correction = if is_rope {
+            separating_speed.max(0.0)
+        } else {
+            separating_speed
+        };
+
+        let pos_correction = dir * stretch / inv_mass;
+        let vel_correction = dir * speed_correction / inv_mass;
+        nudge(self.a, bodies, pos_correction * inv_mass_a, vel_correction * inv_mass_a);
+        nudge(
+            self.b,
+            bodies,
+            -pos_correction * inv_mass_b,
+            -vel_correction * inv_mass_b,
+        );
+    }
+
+    /// XPBD projection of this constraint for one substep of length `h`. `previous` holds every body's position at
+    /// the start of the substep, which spring damping needs. Springs are soft constraints with the inverse of their
+    /// stiffness as compliance, while rods and ropes use `compliance`.
+    pub fn solve_xpbd(&self, bodies: &mut [PhysicsBody], previous: &[Vec3], h: f32, compliance: f32) {
+        let (length, compliance, damping, is_rope) = match self.kind {
+            ConstraintKind::Distance { length } => (length, compliance, 0.0, false),
+            ConstraintKind::Rope { length } => (length, compliance, 0.0, true),
+            ConstraintKind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+            } => (rest_length, 1.0 / stiffness, damping, false),
+        };
+        let (pos_a, _, inv_mass_a) = state(self.a, bodies);
+        let (pos_b, _, inv_mass_b) = state(self.b, bodies);
+        let inv_mass = inv_mass_a + inv_mass_b;
+        if inv_mass == 0.0 {
+            return;
+        }
+
+        let offset = pos_b - pos_a;
+        let dir = offset.normalize_or(Vec3::Y);
+        let stretch = offset.length() - length;
+        if is_rope && stretch <= 0.0 {
+            return;
+        }
+
+        let moved = |anchor: Anchor, pos: Vec3| match anchor {
+            Anchor::Body(i) => pos - previous[i],
+            Anchor::Fixed(_) => Vec3::ZERO,
+        };
+        let separating = (moved(self.b, pos_b) - moved(self.a, pos_a)).dot(dir);
+        let compliance = compliance / (h * h);
+        let gamma = compliance * damping * h;
+        let lambda = -(stretch + gamma * separating) / ((1.0 + gamma) * inv_mass + compliance);
+
+        nudge(self.a, bodies, -dir * lambda * inv_mass_a, Vec3::ZERO);
+        nudge(self.b, bodies, dir * lambda * inv_mass_b, Vec3::ZERO);
+    }
+
+    /// The two ends of the line segment drawn for this constraint.
+    pub fn line_vertices(&self, bodies: &[PhysicsBody]) -> [Vertex; 2] {
+        let color = match self.kind {
+            ConstraintKind::Distance { .. } => DISTANCE_COLOR,
+            ConstraintKind::Spring { .. } => SPRING_COLOR,
+            ConstraintKind::Rope { .. } => ROPE_COLOR,
+        };
+        [self.a, self.b].map(|anchor| Vertex::new(state(anchor, bodies).0, color, Vec3::Z))
+    }
+}
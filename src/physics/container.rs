@@ -0,0 +1,198 @@
+use super::{MotionPath, Scene};
+use crate::BORDER_CENTER;
+use glam::{Quat, Vec3};
+use serde::Deserialize;
+use std::f32::consts::TAU;
+
+/// Back and forth motion along a line, like a shaker table.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Shake {
+    /// Furthest the container gets from where it would otherwise be, either way.
+    pub amplitude: Vec3,
+    /// Times a second it goes back and forth.
+    pub frequency: f32,
+}
+
+/// The border every body is kept inside. It's always a sphere of `BORDER_RADIUS`, but can move along a path, shake
+/// and spin, and bodies bouncing off it pick up the speed of the wall where they hit.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Container {
+    /// Keyframes for the center to follow. Without them it stays at `BORDER_CENTER`.
+    pub path: Option<MotionPath>,
+    /// Shaking on top of wherever the path puts the center.
+    pub shake: Option<Shake>,
+    /// Angular velocity about the center, in radians per second, like a drum turning on its axle.
+    pub spin: Vec3,
+    /// Fraction of the speed a body slides along the wall with that's taken away when it hits, from 0 for a slippery
+    /// wall to 1 for one nothing slides on. A spinning container only drags bodies round with some friction. Used by
+    /// the impulse solver, XPBD uses its own friction for the wall like for every other contact.
+    pub friction: f32,
+
+    /// Where the center is now. This and the rest below are set from the motion above at the start of every step.
+    #[serde(skip)]
+    pub center: Vec3,
+    #[serde(skip)]
+    pub rotation: Quat,
+    /// How fast the center is moving.
+    #[serde(skip)]
+    pub velocity: Vec3,
+}
+
+impl Default for Container {
+    fn default() -> Self {
+        Self {
+            path: None,
+            shake: None,
+            spin: Vec3::ZERO,
+            friction: 0.0,
+            center: BORDER_CENTER,
+            rotation: Quat::IDENTITY,
+            velocity: Vec3::ZERO,
+        }
+    }
+}
+
+impl Container {
+    /// Whether the container goes anywhere or turns at all.
+    pub fn is_moving(&self) -> bool {
+        self.path.is_some() || self.shake.is_some() || self.spin != Vec3::ZERO
+    }
+
+    fn center_at(&self, time: f32) -> Vec3 {
+        let center = self.path.as_ref().map_or(BORDER_CENTER, |path| path.position(time));
+        let shake = self
+            .shake
+            .map_or(Vec3::ZERO, |s| s.amplitude * (TAU * s.frequency * time).sin());
+        center + shake
+    }
+
+    /// Puts the container where its motion says it is at `time`, moving at the speed that gets it to where it will be
+    /// after `dt`.
+    pub fn move_to(&mut self, time: f32, dt: f32) {
+        self.center = self.center_at(time);
+        self.velocity = (self.center_at(time + dt) - self.center) / dt;
+        self.rotation = Quat::from_scaled_axis(self.spin * time);
+    }
+
+    /// Velocity of the wall at `point`, from the container both moving and spinning.
+    pub fn wall_velocity(&self, point: Vec3) -> Vec3 {
+        self.velocity + self.spin.cross(point - self.center)
+    }
+
+    /// Where a point fixed to the container is now, given where it is with the container at rest at `BORDER_CENTER`.
+    pub fn place(&self, point: Vec3) -> Vec3 {
+        self.center + self.rotation * (point - BORDER_CENTER)
+    }
+}
+
+impl Scene {
+    /// Moves the container to where it is at the start of this step.
+    pub(super) fn drive_container(&mut self, dt: f32) {
+        self.container.move_to(self.time, dt);
+        // Moving walls can reach any body, so nothing gets to sleep
+        if self.container.is_moving() {
+            self.wake_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{Interpolation, Keyframe, Solver, Xpbd};
+    use crate::{BORDER_RADIUS, DT};
+    use glam::Vec4;
+
+    /// A ball resting on the bottom of `container`.
+    fn resting_ball(solver: Solver, container: Container) -> Scene {
+        let mut scene = Scene {
+            solver,
+            container,
+            ..Scene::default()
+        };
+        scene.add_ball(0.05, BORDER_CENTER - Vec3::Y * (BORDER_RADIUS - 0.05), Vec4::ONE);
+        scene
+    }
+
+    #[test]
+    fn shaking_throws_balls_up() {
+        let shake = Container {
+            // Accelerating at up to 8g, so the floor drops away faster than the ball falls
+            shake: Some(Shake {
+                amplitude: Vec3::Y * 0.02,
+                frequency: 10.0,
+            }),
+            ..Container::default()
+        };
+        for (container, thrown) in [(Container::default(), false), (shake, true)] {
+            let mut scene = resting_ball(Solver::Impulse, container);
+            let mut highest = 0.0f32;
+            for _ in 0..1000 {
+                scene.update_physics(DT);
+                let floor = scene.container.center.y - BORDER_RADIUS;
+                highest = highest.max(scene.physics_bodies[0].pos.y - 0.05 - floor);
+            }
+            assert_eq!(highest > 0.01, thrown, "ball got {highest} off the floor");
+        }
+    }
+
+    #[test]
+    fn spinning_drum_carries_balls_up_the_side() {
+        for solver in [Solver::Impulse, Solver::Xpbd(Xpbd::default())] {
+            let drum = Container {
+                spin: Vec3::Z * 3.0,
+                friction: 0.5,
+                ..Container::default()
+            };
+            let mut scene = resting_ball(solver.clone(), drum);
+            let mut highest = f32::MIN;
+            for _ in 0..1000 {
+                scene.update_physics(DT);
+                highest = highest.max(scene.physics_bodies[0].pos.y);
+            }
+            let bottom = BORDER_CENTER.y - BORDER_RADIUS + 0.05;
+            assert!(highest > bottom + 0.05, "{solver:?} only got the ball to {highest}");
+        }
+    }
+
+    #[test]
+    fn bodies_and_border_mesh_follow_the_container() {
+        let path = MotionPath {
+            keyframes: vec![
+                Keyframe {
+                    time: 0.0,
+                    pos: BORDER_CENTER,
+                },
+                Keyframe {
+                    time: 1.0,
+                    pos: BORDER_CENTER + Vec3::X * 0.5,
+                },
+            ],
+            interpolation: Interpolation::Linear,
+            looping: false,
+        };
+        let mut scene = resting_ball(
+            Solver::Impulse,
+            Container {
+                path: Some(path),
+                spin: Vec3::Y,
+                ..Container::default()
+            },
+        );
+        scene.create_3d_border(BORDER_RADIUS, 5, BORDER_CENTER);
+        for _ in 0..1500 {
+            scene.update_physics(DT);
+            let b = &scene.physics_bodies[0];
+            assert!(b.pos.distance(scene.container.center) + b.radius <= BORDER_RADIUS + 1e-5);
+        }
+        assert_eq!(scene.container.center, BORDER_CENTER + Vec3::X * 0.5);
+
+        let at_rest = scene.static_meshes.iter().flat_map(|m| &m.vertices);
+        for (vertex, moved) in at_rest.zip(scene.static_vertices()) {
+            let (vertex, moved) = (Vec3::from(vertex.position), Vec3::from(moved.position));
+            assert!(moved.distance(scene.container.place(vertex)) < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::grid::SpatialGrid;
+use super::PhysicsBody;
+use glam::Vec3;
+
+/// Coulomb forces between charged bodies. Only pairs closer than `cutoff` interact, which keeps the cost down to the
+/// neighbours found by a grid instead of every pair.
+#[derive(Clone, Debug)]
+pub struct Electrostatics {
+    /// Coulomb constant in scene units.
+    pub k: f32,
+    pub cutoff: f32,
+    /// Keeps the force finite when two charges get very close, like the softening used for gravitation.
+    pub softening: f32,
+}
+
+impl Electrostatics {
+    /// Force on every body from the charged bodies around it. Like charges repel, opposite charges attract.
+    pub fn forces(&self, bodies: &[PhysicsBody]) -> Vec<Vec3> {
+        let mut forces = vec![Vec3::ZERO; bodies.len()];
+        let charged = bodies.iter().enumerate().filter(|(_, b)| b.charge != 0.0);
+        let grid = SpatialGrid::new(self.cutoff, charged.map(|(i, b)| (i, b.pos)));
+
+        grid.for_each_pair(|a, b| {
+            let offset = bodies[b].pos - bodies[a].pos;
+            let distance_squared = offset.length_squared();
+            if distance_squared >= self.cutoff * self.cutoff {
+                return;
+            }
+            let softened = distance_squared + self.softening * self.softening;
+            let force = self.k * bodies[a].charge * bodies[b].charge * offset / (softened * softened.sqrt());
+            forces[a] -= force;
+            forces[b] += force;
+        });
+        forces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSOFTENED: Electrostatics = Electrostatics {
+        k: 2.0,
+        cutoff: 0.5,
+        softening: 0.0,
+    };
+
+    /// Two bodies `distance` apart along x, the first at the origin.
+    fn pair(charges: [f32; 2], distance: f32) -> Vec<PhysicsBody> {
+        [Vec3::ZERO, Vec3::X * distance]
+            .into_iter()
+            .zip(charges)
+            .map(|(pos, charge)| {
+                let mut body = PhysicsBody::new(pos, 0.01);
+                body.charge = charge;
+                body
+            })
+            .collect()
+    }
+
+    #[test]
+    fn like_charges_repel_and_opposite_charges_attract() {
+        let forces = UNSOFTENED.forces(&pair([1.0, 2.0], 0.2));
+        assert!(forces[0].x < 0.0 && forces[1].x > 0.0);
+        let forces = UNSOFTENED.forces(&pair([-1.0, -2.0], 0.2));
+        assert!(forces[0].x < 0.0 && forces[1].x > 0.0);
+        let forces = UNSOFTENED.forces(&pair([1.0, -2.0], 0.2));
+        assert!(forces[0].x > 0.0 && forces[1].x < 0.0);
+    }
+
+    #[test]
+    fn follows_inverse_square_inside_cutoff() {
+        for distance in [0.05, 0.1, 0.3, 0.49] {
+            let forces = UNSOFTENED.forces(&pair([0.5, -3.0], distance));
+            let expected = UNSOFTENED.k * 0.5 * 3.0 / (distance * distance);
+            assert!(
+                (forces[0].x - expected).abs() < expected * 1e-5,
+                "{} at {distance}, expected {expected}",
+                forces[0].x
+            );
+            assert_eq!(forces[0], -forces[1]);
+            assert_eq!(forces[0].y, 0.0);
+        }
+    }
+
+    #[test]
+    fn nothing_beyond_cutoff() {
+        for distance in [0.5, 0.51, 2.0] {
+            assert_eq!(UNSOFTENED.forces(&pair([1.0, 1.0], distance)), [Vec3::ZERO; 2]);
+        }
+    }
+
+    #[test]
+    fn neutral_bodies_feel_nothing() {
+        let mut bodies = pair([1.0, 0.0], 0.1);
+        bodies.push(PhysicsBody::new(Vec3::Y * 0.1, 0.01));
+        assert_eq!(UNSOFTENED.forces(&bodies), [Vec3::ZERO; 3]);
+    }
+}
@@ -0,0 +1,129 @@
+use super::PhysicsBody;
+use glam::Vec3;
+use std::fmt;
+use std::sync::Arc;
+
+/// Something that pushes bodies around depending on where they are and when. Every field in a scene is evaluated for
+/// every body each step and the results are summed.
+pub trait ForceField: fmt::Debug + Send + Sync + ForceFieldClone {
+    /// Force on `body` at simulation time `time`.
+    fn force(&self, body: &PhysicsBody, time: f32) -> Vec3;
+}
+
+/// Lets scenes holding boxed fields stay `Clone`. Implemented for every field that is `Clone` itself.
+pub trait ForceFieldClone {
+    fn clone_box(&self) -> Box<dyn ForceField>;
+}
+
+impl<T: ForceField + Clone + 'static> ForceFieldClone for T {
+    fn clone_box(&self) -> Box<dyn ForceField> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn ForceField> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// The same acceleration everywhere, like gravity or a steady wind.
+#[derive(Clone, Debug)]
+pub struct Uniform {
+    pub acceleration: Vec3,
+}
+
+impl ForceField for Uniform {
+    fn force(&self, body: &PhysicsBody, _time: f32) -> Vec3 {
+        self.acceleration * body.mass
+    }
+}
+
+/// How a field weakens with distance from its center.
+#[derive(Clone, Copy, Debug)]
+pub enum Falloff {
+    Constant,
+    InverseLinear,
+    InverseSquare,
+}
+
+/// Pulls bodies towards `center`, or pushes them away when `strength` is negative. `strength` is the acceleration
+/// at unit distance, and distances are clamped to `softening` so bodies passing through the center don't get flung
+/// out.
+#[derive(Clone, Debug)]
+pub struct PointAttractor {
+    pub center: Vec3,
+    pub strength: f32,
+    pub falloff: Falloff,
+    pub softening: f32,
+}
+
+impl ForceField for PointAttractor {
+    fn force(&self, body: &PhysicsBody, _time: f32) -> Vec3 {
+        let offset = self.center - body.pos;
+        let distance = offset.length().max(self.softening);
+        let scale = match self.falloff {
+            Falloff::Constant => 1.0,
+            Falloff::InverseLinear => 1.0 / distance,
+            Falloff::InverseSquare => 1.0 / (distance * distance),
+        };
+        offset.normalize_or_zero() * self.strength * scale * body.mass
+    }
+}
+
+/// Swirls bodies around the line through `center` along `axis`. The tangential acceleration is `strength` on the
+/// axis and fades linearly to nothing at `radius`. A positive `pull` also draws bodies in towards the axis, which
+/// keeps them from being flung out of the swirl.
+#[derive(Clone, Debug)]
+pub struct Vortex {
+    pub center: Vec3,
+    pub axis: Vec3,
+    pub strength: f32,
+    pub radius: f32,
+    pub pull: f32,
+}
+
+impl ForceField for Vortex {
+    fn force(&self, body: &PhysicsBody, _time: f32) -> Vec3 {
+        let axis = self.axis.normalize_or_zero();
+        let offset = body.pos - self.center;
+        let radial = offset - axis * offset.dot(axis);
+        let distance = radial.length();
+        if distance >= self.radius {
+            return Vec3::ZERO;
+        }
+        let falloff = 1.0 - distance / self.radius;
+        let tangent = axis.cross(radial).normalize_or_zero();
+        (tangent * self.strength - radial.normalize_or_zero() * self.pull) * falloff * body.mass
+    }
+}
+
+/// Scales another field by a function of simulation time, e.g. to pulse an attractor or slowly ramp up a wind.
+#[derive(Clone)]
+pub struct TimeVarying {
+    pub field: Box<dyn ForceField>,
+    pub scale: Arc<dyn Fn(f32) -> f32 + Send + Sync>,
+}
+
+impl TimeVarying {
+    pub fn new(field: impl ForceField + 'static, scale: impl Fn(f32) -> f32 + Send + Sync + 'static) -> Self {
+        Self {
+            field: Box::new(field),
+            scale: Arc::new(scale),
+        }
+    }
+}
+
+impl fmt::Debug for TimeVarying {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimeVarying")
+            .field("field", &self.field)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ForceField for TimeVarying {
+    fn force(&self, body: &PhysicsBody, time: f32) -> Vec3 {
+        self.field.force(body, time) * (self.scale)(time)
+    }
+}
@@ -0,0 +1,149 @@
+use glam::Vec3;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+
+/// Uniform grid bucketing bodies by position, used to find the pairs that are close enough to interact without
+/// testing every pair. Cells are kept in a sorted map so pairs always come out in the same order.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: BTreeMap<[i32; 3], Vec<usize>>,
+}
+
+/// Neighbouring cells that come after a cell in key order. Visiting only these from every cell reaches each pair of
+/// neighbouring cells exactly once.
+const FORWARD_NEIGHBOURS: [[i32; 3]; 13] = [
+    [0, 0, 1],
+    [0, 1, -1],
+    [0, 1, 0],
+    [0, 1, 1],
+    [1, -1, -1],
+    [1, -1, 0],
+    [1, -1, 1],
+    [1, 0, -1],
+    [1, 0, 0],
+    [1, 0, 1],
+    [1, 1, -1],
+    [1, 1, 0],
+    [1, 1, 1],
+];
+
+impl SpatialGrid {
+    /// Buckets `bodies`, given as index and position, into cells of `cell_size`.
+    pub fn new(cell_size: f32, bodies: impl IntoIterator<Item = (usize, Vec3)>) -> Self {
+        let mut grid = Self {
+            cell_size,
+            cells: BTreeMap::new(),
+        };
+        for (i, pos) in bodies {
+            grid.cells.entry(grid.cell(pos)).or_default().push(i);
+        }
+        grid
+    }
+
+    fn cell(&self, pos: Vec3) -> [i32; 3] {
+        (pos / self.cell_size).floor().as_ivec3().to_array()
+    }
+
+    /// Calls `f` once for every pair of bodies in the same or neighbouring cells. Any two bodies closer than the cell
+    /// size are guaranteed to be among them.
+    pub fn for_each_pair(&self, mut f: impl FnMut(usize, usize)) {
+        for (&key, bodies) in &self.cells {
+            self.for_each_pair_from(key, bodies, &mut f);
+        }
+    }
+
+    /// The pairs `for_each_pair` would visit for which `keep` returns true, in the same order. With the `parallel`
+    /// feature, cells are searched on separate threads.
+    pub fn pairs(&self, keep: impl Fn(usize, usize) -> bool + Sync) -> Vec<(usize, usize)> {
+        let cell_pairs = |(&key, bodies): (&[i32; 3], &Vec<usize>)| {
+            let mut pairs = Vec::new();
+            self.for_each_pair_from(key, bodies, |a, b| {
+                if keep(a, b) {
+                    pairs.push((a, b));
+                }
+            });
+            pairs
+        };
+        #[cfg(feature = "parallel")]
+        return self.cells.par_iter().flat_map_iter(cell_pairs).collect();
+        #[cfg(not(feature = "parallel"))]
+        return self.cells.iter().flat_map(cell_pairs).collect();
+    }
+
+    /// Pairs within the cell at `key`, holding `bodies`, and between it and the neighbouring cells after it.
+    fn for_each_pair_from(&self, [x, y, z]: [i32; 3], bodies: &[usize], mut f: impl FnMut(usize, usize)) {
+        for (n, &a) in bodies.iter().enumerate() {
+            for &b in &bodies[n + 1..] {
+                f(a, b);
+            }
+        }
+        for [dx, dy, dz] in FORWARD_NEIGHBOURS {
+            // Bodies far enough out, like one that went infinite, sit in the last cell, which has nothing past it
+            let (Some(nx), Some(ny), Some(nz)) = (x.checked_add(dx), y.checked_add(dy), z.checked_add(dz)) else {
+                continue;
+            };
+            let Some(neighbours) = self.cells.get(&[nx, ny, nz]) else {
+                continue;
+            };
+            for &a in bodies {
+                for &b in neighbours {
+                    f(a, b);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbour_pairs_match_brute_force() {
+        let mut state = 0x9e37_79b9_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+        // Centered on the origin, so cells on both sides of zero are covered
+        let positions = (0..400)
+            .map(|_| (Vec3::new(next(), next(), next()) - 0.5) * 2.0)
+            .collect::<Vec<_>>();
+        let cell_size = 0.15;
+        let close = |a: usize, b: usize| positions[a].distance(positions[b]) < cell_size;
+
+        let grid = SpatialGrid::new(cell_size, positions.iter().copied().enumerate());
+        let mut found = grid.pairs(close);
+        for pair in &mut found {
+            *pair = (pair.0.min(pair.1), pair.0.max(pair.1));
+        }
+        found.sort_unstable();
+
+        let mut expected = Vec::new();
+        for a in 0..positions.len() {
+            for b in a + 1..positions.len() {
+                if close(a, b) {
+                    expected.push((a, b));
+                }
+            }
+        }
+        assert!(
+            expected.len() > 100,
+            "only {} close pairs, not much of a test",
+            expected.len()
+        );
+        assert_eq!(found, expected);
+
+        // No pair is visited twice, close or not
+        let mut visited = Vec::new();
+        grid.for_each_pair(|a, b| visited.push((a.min(b), a.max(b))));
+        let visits = visited.len();
+        visited.sort_unstable();
+        visited.dedup();
+        assert_eq!(visited.len(), visits);
+    }
+}
@@ -0,0 +1,236 @@
+use super::{Mass, Scene};
+use glam::{Vec3, Vec4};
+use serde::Deserialize;
+
+/// Where a kinematic body should be at `time`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    pub time: f32,
+    pub pos: Vec3,
+}
+
+/// How a motion path gets from one keyframe to the next.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// Straight lines at constant speed, turning sharply at every keyframe.
+    #[default]
+    Linear,
+    /// A Catmull-Rom curve through the keyframes, so the body changes direction without jerking.
+    Smooth,
+}
+
+/// Prescribed motion through a list of keyframes, in order of time.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MotionPath {
+    pub keyframes: Vec<Keyframe>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Go round again after the last keyframe instead of stopping there. The last keyframe should be back where the
+    /// first one is, or the body jumps between them.
+    #[serde(default)]
+    pub looping: bool,
+}
+
+impl MotionPath {
+    /// Position along the path at `time`. Before the first keyframe, and after the last one unless looping, the body
+    /// waits at the nearest end. `keyframes` must not be empty.
+    pub fn position(&self, time: f32) -> Vec3 {
+        let first = self.keyframes[0];
+        let last = self.keyframes[self.keyframes.len() - 1];
+        let time = if self.looping && self.period() > 0.0 {
+            first.time + (time - first.time).rem_euclid(self.period())
+        } else {
+            time.clamp(first.time, last.time)
+        };
+
+        // Index of the keyframe starting the segment `time` falls in
+        let segment = (self.keyframes.partition_point(|k| k.time <= time).saturating_sub(1))
+            .min(self.keyframes.len().saturating_sub(2));
+        let (start, end) = (self.keyframe(segment as isize), self.keyframe(segment as isize + 1));
+        let duration = end.time - start.time;
+        if duration <= 0.0 {
+            return end.pos;
+        }
+        let s = (time - start.time) / duration;
+        match self.interpolation {
+            Interpolation::Linear => start.pos.lerp(end.pos, s),
+            Interpolation::Smooth => {
+                // Cubic Hermite curve, with tangents from the keyframes either side
+                let tangent_start = self.tangent(segment as isize) * duration;
+                let tangent_end = self.tangent(segment as isize + 1) * duration;
+                let (s2, s3) = (s * s, s * s * s);
+                start.pos * (2.0 * s3 - 3.0 * s2 + 1.0)
+                    + tangent_start * (s3 - 2.0 * s2 + s)
+                    + end.pos * (-2.0 * s3 + 3.0 * s2)
+                    + tangent_end * (s3 - s2)
+            }
+        }
+    }
+
+    fn period(&self) -> f32 {
+        self.keyframes[self.keyframes.len() - 1].time - self.keyframes[0].time
+    }
+
+    /// Keyframe `i`, continuing past either end into the previous or next time round when looping and staying at the
+    /// end otherwise.
+    fn keyframe(&self, i: isize) -> Keyframe {
+        let last = self.keyframes.len() as isize - 1;
+        if !self.looping || last == 0 {
+            return self.keyframes[i.clamp(0, last) as usize];
+        }
+        let keyframe = self.keyframes[i.rem_euclid(last) as usize];
+        Keyframe {
+            time: keyframe.time + i.div_euclid(last) as f32 * self.period(),
+            pos: keyframe.pos,
+        }
+    }
+
+    /// Velocity through keyframe `i` for smooth interpolation.
+    fn tangent(&self, i: isize) -> Vec3 {
+        let (before, after) = (self.keyframe(i - 1), self.keyframe(i + 1));
+        let duration = after.time - before.time;
+        if duration > 0.0 {
+            (after.pos - before.pos) / duration
+        } else {
+            Vec3::ZERO
+        }
+    }
+}
+
+/// A body moved along a path instead of by the simulation. It should be immovable, so it pushes other bodies aside
+/// without being pushed back.
+#[derive(Clone, Debug)]
+pub struct Kinematic {
+    pub body: usize,
+    pub path: MotionPath,
+}
+
+impl Scene {
+    /// Adds an immovable ball that follows `path`, and returns its index into `physics_bodies`.
+    pub fn add_kinematic_ball(&mut self, radius: f32, color: Vec4, path: MotionPath) -> usize {
+        let ball = self.add_ball_with_mass(radius, path.position(self.time), color, Mass::Immovable);
+        self.kinematic.push(Kinematic { body: ball, path });
+        ball
+    }
+
+    /// Puts every kinematic body where its path says it is now, moving at the speed that gets it to where the path
+    /// will be after `dt`. Contacts then see how fast it's really going, and push other bodies along with it.
+    pub(super) fn drive_kinematic(&mut self, dt: f32) {
+        for k in 0..self.kinematic.len() {
+            let Kinematic { body, ref path } = self.kinematic[k];
+            let pos = path.position(self.time);
+            let velocity = (path.position(self.time + dt) - pos) / dt;
+
+            let b = &mut self.physics_bodies[body];
+            b.pos = pos;
+            b.velocity = velocity;
+            // Whatever is resting on it starts moving too
+            if b.asleep && velocity != Vec3::ZERO {
+                self.wake(body);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{Solver, Xpbd};
+    use crate::{BORDER_CENTER, DT};
+
+    fn path(interpolation: Interpolation, looping: bool) -> MotionPath {
+        let keyframes = [
+            (0.0, Vec3::ZERO),
+            (1.0, Vec3::X),
+            (3.0, Vec3::new(1.0, 1.0, 0.0)),
+            (4.0, Vec3::ZERO),
+        ];
+        MotionPath {
+            keyframes: keyframes.map(|(time, pos)| Keyframe { time, pos }).to_vec(),
+            interpolation,
+            looping,
+        }
+    }
+
+    #[test]
+    fn passes_through_every_keyframe() {
+        for interpolation in [Interpolation::Linear, Interpolation::Smooth] {
+            for looping in [false, true] {
+                let path = path(interpolation, looping);
+                for k in &path.keyframes {
+                    assert!(path.position(k.time).distance(k.pos) < 1e-6, "{path:?} at {}", k.time);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn waits_at_the_ends_or_loops() {
+        let linear = path(Interpolation::Linear, false);
+        assert_eq!(linear.position(-1.0), Vec3::ZERO);
+        assert_eq!(linear.position(5.0), Vec3::ZERO);
+        assert_eq!(linear.position(2.0), Vec3::new(1.0, 0.5, 0.0));
+
+        let looping = path(Interpolation::Smooth, true);
+        for time in [0.3, 1.7, 3.2] {
+            assert!(looping.position(time).distance(looping.position(time + 8.0)) < 1e-5);
+            assert!(looping.position(time).distance(looping.position(time - 4.0)) < 1e-5);
+        }
+    }
+
+    #[test]
+    fn smooth_path_has_no_corners() {
+        let path = path(Interpolation::Smooth, true);
+        let velocity = |time: f32| (path.position(time + 5e-4) - path.position(time - 5e-4)) / 1e-3;
+        // Through a keyframe, and through the point where the loop starts over
+        for time in [1.0, 4.0] {
+            let (before, after) = (velocity(time - 2e-3), velocity(time + 2e-3));
+            assert!(before.distance(after) < 0.05, "{before} then {after} at {time}");
+        }
+    }
+
+    #[test]
+    fn paddle_pushes_balls_without_slowing_down() {
+        for solver in [Solver::Impulse, Solver::Xpbd(Xpbd::default())] {
+            let mut scene = Scene {
+                solver,
+                gravity: Vec3::ZERO,
+                ..Scene::default()
+            };
+            let start = BORDER_CENTER - Vec3::X * 0.5;
+            let paddle = MotionPath {
+                keyframes: vec![
+                    Keyframe { time: 0.0, pos: start },
+                    Keyframe {
+                        time: 1.0,
+                        pos: start + Vec3::X,
+                    },
+                ],
+                interpolation: Interpolation::Linear,
+                looping: false,
+            };
+            let paddle = scene.add_kinematic_ball(0.1, Vec4::ONE, paddle);
+            let ball = scene.add_ball(0.05, BORDER_CENTER - Vec3::X * 0.2, Vec4::ONE);
+
+            for _ in 0..(0.5 / DT) as usize {
+                scene.update_physics(DT);
+            }
+            let (paddle, ball) = (&scene.physics_bodies[paddle], &scene.physics_bodies[ball]);
+            assert!(
+                (paddle.pos.x - (start.x + scene.time)).abs() < 1e-4,
+                "paddle at {}",
+                paddle.pos
+            );
+            assert!(
+                (paddle.velocity.x - 1.0).abs() < 1e-3,
+                "paddle moving at {}",
+                paddle.velocity
+            );
+            assert!(ball.pos.x > paddle.pos.x + 0.149, "ball at {} left behind", ball.pos);
+            assert!(ball.velocity.x >= 1.0, "ball moving at {}", ball.velocity);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use super::parallel::map_bodies;
+use super::PhysicsBody;
+use glam::Vec3;
+use std::ops::Range;
+
+/// Deeper than this, cells are smaller than anything a float can tell apart around the scene, so bodies left
+/// sharing a cell are stored together in one leaf.
+const MAX_DEPTH: usize = 32;
+
+/// With this few bodies, building the tree costs more than summing over every pair.
+const BRUTE_FORCE_BELOW: usize = 64;
+
+/// Newtonian attraction between every pair of bodies.
+#[derive(Clone, Debug)]
+pub struct Gravitation {
+    /// Gravitational constant in scene units.
+    pub g: f32,
+    /// Plummer softening length. Keeps the force finite when two bodies get very close.
+    pub softening: f32,
+    /// Barnes–Hut opening angle. Cells that look smaller than this from a body are treated as a single mass at their
+    /// center of mass. Zero opens every cell, giving the exact pairwise result.
+    pub theta: f32,
+}
+
+/// Mass a body pulls others with. Immovable bodies have infinite mass, and would pull infinitely hard, so they're left
+/// out instead.
+fn source_mass(body: &PhysicsBody) -> f32 {
+    if body.mass.is_finite() {
+        body.mass
+    } else {
+        0.0
+    }
+}
+
+impl Gravitation {
+    /// Acceleration on a body at `pos` due to `mass` at `source`.
+    fn acceleration(&self, pos: Vec3, source: Vec3, mass: f32) -> Vec3 {
+        let offset = source - pos;
+        let distance_squared = offset.length_squared() + self.softening * self.softening;
+        self.g * mass * offset / (distance_squared * distance_squared.sqrt())
+    }
+
+    /// Acceleration on every body from all the others.
+    pub fn accelerations(&self, bodies: &[PhysicsBody]) -> Vec<Vec3> {
+        if bodies.len() < BRUTE_FORCE_BELOW {
+            self.brute_force_accelerations(bodies)
+        } else {
+            self.barnes_hut_accelerations(bodies)
+        }
+    }
+
+    /// Approximates `brute_force_accelerations` in O(n log n) with a Barnes–Hut octree.
+    pub fn barnes_hut_accelerations(&self, bodies: &[PhysicsBody]) -> Vec<Vec3> {
+        let tree = Octree::new(bodies);
+        map_bodies(bodies, |i, _| tree.acceleration(i, bodies, self))
+    }
+
+    /// Exact O(n²) acceleration on every body from all the others.
+    pub fn brute_force_accelerations(&self, bodies: &[PhysicsBody]) -> Vec<Vec3> {
+        map_bodies(bodies, |i, b| {
+            bodies
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, other)| self.acceleration(b.pos, other.pos, source_mass(other)))
+                .sum()
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// Half the side length of the cube this node covers.
+    half_size: f32,
+    mass: f32,
+    center_of_mass: Vec3,
+    /// Indices of the non-empty children. Empty for leaves.
+    children: Vec<usize>,
+    /// Range of `Octree::order` holding the bodies in this node.
+    bodies: Range<usize>,
+}
+
+/// Octree over the bodies, with every node summarised by its total mass and center of mass.
+#[derive(Clone, Debug)]
+struct Octree {
+    nodes: Vec<Node>,
+    /// Body indices, ordered so every node's bodies are contiguous.
+    order: Vec<usize>,
+}
+
+impl Octree {
+    fn new(bodies: &[PhysicsBody]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            order: (0..bodies.len()).collect(),
+        };
+        if bodies.is_empty() {
+            return tree;
+        }
+
+        let min = bodies.iter().fold(Vec3::INFINITY, |min, b| min.min(b.pos));
+        let max = bodies.iter().fold(Vec3::NEG_INFINITY, |max, b| max.max(b.pos));
+        let center = (min + max) / 2.0;
+        let half_size = ((max - min).max_element() / 2.0).max(f32::EPSILON);
+        tree.build(bodies, 0..bodies.len(), center, half_size, 0);
+        tree
+    }
+
+    /// Adds the node covering the cube at `center` for the bodies in `order[range]`, returning its index.
+    fn build(
+        &mut self,
+        bodies: &[PhysicsBody],
+        range: Range<usize>,
+        center: Vec3,
+        half_size: f32,
+        depth: usize,
+    ) -> usize {
+        let index = self.nodes.len();
+        let (mass, weighted) = self.order[range.clone()].iter().fold((0.0, Vec3::ZERO), |(m, w), &i| {
+            (m + source_mass(&bodies[i]), w + bodies[i].pos * source_mass(&bodies[i]))
+        });
+        self.nodes.push(Node {
+            half_size,
+            mass,
+            // A node holding only immovable bodies has no mass to center, and pulls on nothing wherever it's put
+            center_of_mass: if mass > 0.0 { weighted / mass } else { center },
+            children: Vec::new(),
+            bodies: range.clone(),
+        });
+
+        if range.len() <= 1 || depth == MAX_DEPTH {
+            return index;
+        }
+
+        let octant = |pos: Vec3| {
+            usize::from(pos.x >= center.x) | usize::from(pos.y >= center.y) << 1 | usize::from(pos.z >= center.z) << 2
+        };
+        self.order[range.clone()].sort_unstable_by_key(|&i| octant(bodies[i].pos));
+
+        let mut start = range.start;
+        let mut children = Vec::new();
+        while start < range.end {
+            let current = octant(bodies[self.order[start]].pos);
+            let end = start + self.order[start..range.end].partition_point(|&i| octant(bodies[i].pos) == current);
+            let sign = |bit: usize| if current & bit != 0 { 1.0 } else { -1.0 };
+            let child_center = center + Vec3::new(sign(1), sign(2), sign(4)) * half_size / 2.0;
+            children.push(self.build(bodies, start..end, child_center, half_size / 2.0, depth + 1));
+            start = end;
+        }
+        self.nodes[index].children = children;
+        index
+    }
+
+    fn acceleration(&self, body: usize, bodies: &[PhysicsBody], settings: &Gravitation) -> Vec3 {
+        let pos = bodies[body].pos;
+        let mut acceleration = Vec3::ZERO;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.children.is_empty() {
+                for &other in self.order[node.bodies.clone()].iter().filter(|&&other| other != body) {
+                    acceleration += settings.acceleration(pos, bodies[other].pos, source_mass(&bodies[other]));
+                }
+                continue;
+            }
+
+            let distance = pos.distance(node.center_of_mass);
+            if 2.0 * node.half_size < settings.theta * distance {
+                acceleration += settings.acceleration(pos, node.center_of_mass, node.mass);
+            } else {
+                stack.extend(&node.children);
+            }
+        }
+        acceleration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic cloud of bodies with varying masses, clustered enough that the tree ends up a few levels deep.
+    fn cloud(count: usize) -> Vec<PhysicsBody> {
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+        (0..count)
+            .map(|i| {
+                let cluster = Vec3::new((i % 3) as f32 - 1.0, 0.0, 0.0) * 0.5;
+                let offset = Vec3::new(next(), next(), next()) - 0.5;
+                let mut body = PhysicsBody::new(cluster + offset * 0.4, 0.01);
+                body.mass = 0.5 + next();
+                body
+            })
+            .collect()
+    }
+
+    fn gravitation(theta: f32) -> Gravitation {
+        Gravitation {
+            g: 1.0,
+            softening: 0.01,
+            theta,
+        }
+    }
+
+    #[test]
+    fn barnes_hut_matches_brute_force() {
+        let bodies = cloud(500);
+        let settings = gravitation(0.5);
+        let approx = settings.barnes_hut_accelerations(&bodies);
+        let exact = settings.brute_force_accelerations(&bodies);
+
+        // Where the pulls from every side nearly cancel, a small absolute error is a large relative one, so errors
+        // are measured against the typical acceleration
+        let rms = (exact.iter().map(|e| e.length_squared()).sum::<f32>() / exact.len() as f32).sqrt();
+        let errors = approx
+            .iter()
+            .zip(&exact)
+            .map(|(a, e)| (*a - *e).length() / rms)
+            .collect::<Vec<_>>();
+        let mean = errors.iter().sum::<f32>() / errors.len() as f32;
+        let max = errors.iter().copied().fold(0.0, f32::max);
+        assert!(mean < 0.01, "mean relative error {mean}");
+        assert!(max < 0.05, "max relative error {max}");
+    }
+
+    #[test]
+    fn zero_theta_is_exact() {
+        let bodies = cloud(100);
+        let settings = gravitation(0.0);
+        for (a, e) in settings
+            .accelerations(&bodies)
+            .iter()
+            .zip(settings.brute_force_accelerations(&bodies))
+        {
+            assert!((*a - e).length() <= 1e-4 * e.length(), "{a} != {e}");
+        }
+    }
+
+    #[test]
+    fn coincident_bodies_share_a_leaf() {
+        let mut bodies = cloud(10);
+        bodies.extend(std::iter::repeat_n(PhysicsBody::new(Vec3::splat(0.1), 0.01), 3));
+        let settings = gravitation(0.5);
+        let approx = settings.barnes_hut_accelerations(&bodies);
+        let exact = settings.brute_force_accelerations(&bodies);
+        for (a, e) in approx.iter().zip(&exact) {
+            assert!(a.is_finite());
+            assert!((*a - *e).length() <= 0.05 * e.length(), "{a} != {e}");
+        }
+    }
+}
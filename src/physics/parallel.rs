@@ -0,0 +1,208 @@
+use super::PhysicsBody;
+#[cfg(feature = "parallel")]
+use super::Scene;
+#[cfg(feature = "parallel")]
+use glam::Vec3;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Runs `f` on every body, along with its index. With the `parallel` feature, bodies are split across threads.
+pub fn for_each_body(bodies: &mut [PhysicsBody], f: impl Fn(usize, &mut PhysicsBody) + Send + Sync) {
+    #[cfg(feature = "parallel")]
+    bodies.par_iter_mut().enumerate().for_each(|(i, b)| f(i, b));
+    #[cfg(not(feature = "parallel"))]
+    bodies.iter_mut().enumerate().for_each(|(i, b)| f(i, b));
+}
+
+/// Collects `f` of every body, along with its index. With the `parallel` feature, bodies are split across threads.
+pub fn map_bodies<T: Send>(bodies: &[PhysicsBody], f: impl Fn(usize, &PhysicsBody) -> T + Send + Sync) -> Vec<T> {
+    #[cfg(feature = "parallel")]
+    return bodies.par_iter().enumerate().map(|(i, b)| f(i, b)).collect();
+    #[cfg(not(feature = "parallel"))]
+    return bodies.iter().enumerate().map(|(i, b)| f(i, b)).collect();
+}
+
+/// How the impulse solver splits contacts across threads. Both give exactly the same result whatever the number of
+/// threads, since no contact's outcome depends on which thread got to it first.
+#[cfg(feature = "parallel")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContactSolver {
+    /// Contacts are split into colors where no two contacts share a body. Colors are solved one after another and
+    /// the contacts within one all at once, so later colors see what earlier ones did, like the serial solver.
+    #[default]
+    Colored,
+    /// Every contact is solved against the same starting state and each body moves by the average of the corrections
+    /// it got. Needs no coloring and has more to run at once, but crowded bodies lose more energy and overlap a little.
+    Jacobi,
+}
+
+#[cfg(feature = "parallel")]
+impl ContactSolver {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Colored => Self::Jacobi,
+            Self::Jacobi => Self::Colored,
+        }
+    }
+}
+
+/// Splits `pairs` into colors where every body appears at most once, keeping the pairs in order within each color.
+#[cfg(feature = "parallel")]
+fn color(pairs: &[(usize, usize)], body_count: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut colors = Vec::new();
+    let mut remaining = pairs.to_vec();
+    let mut used = vec![false; body_count];
+    while !remaining.is_empty() {
+        let mut color = Vec::new();
+        let mut next = Vec::new();
+        for (a, b) in remaining {
+            if used[a] || used[b] {
+                next.push((a, b));
+            } else {
+                used[a] = true;
+                used[b] = true;
+                color.push((a, b));
+            }
+        }
+        for &(a, b) in &color {
+            used[a] = false;
+            used[b] = false;
+        }
+        colors.push(color);
+        remaining = next;
+    }
+    colors
+}
+
+#[cfg(feature = "parallel")]
+impl Scene {
+    /// Resolves overlaps between `pairs` of bodies across threads with `contact_solver`, returning the pairs that
+    /// were touching.
+    pub(super) fn collide_pairs_parallel(&mut self, pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let collide = |bodies: &[PhysicsBody], &(a, b): &(usize, usize)| {
+            let (mut body_a, mut body_b) = (bodies[a].clone(), bodies[b].clone());
+            let touching = body_a.collide_with(&mut body_b);
+            (body_a, body_b, touching)
+        };
+
+        let mut touching = Vec::new();
+        match self.contact_solver {
+            ContactSolver::Colored => {
+                for color in color(pairs, self.physics_bodies.len()) {
+                    let results = color
+                        .par_iter()
+                        .map(|pair| collide(&self.physics_bodies, pair))
+                        .collect::<Vec<_>>();
+                    for (&(a, b), (body_a, body_b, touched)) in color.iter().zip(results) {
+                        self.physics_bodies[a] = body_a;
+                        self.physics_bodies[b] = body_b;
+                        if touched {
+                            touching.push((a, b));
+                        }
+                    }
+                }
+            }
+            ContactSolver::Jacobi => {
+                let results = pairs
+                    .par_iter()
+                    .map(|pair| collide(&self.physics_bodies, pair))
+                    .collect::<Vec<_>>();
+                // Summed in pair order, so rounding doesn't depend on how the work was split
+                let mut corrections = vec![(Vec3::ZERO, Vec3::ZERO, 0.0); self.physics_bodies.len()];
+                for (&(a, b), (body_a, body_b, touched)) in pairs.iter().zip(results) {
+                    for (i, moved) in [(a, body_a), (b, body_b)] {
+                        let correction = &mut corrections[i];
+                        correction.0 += moved.pos - self.physics_bodies[i].pos;
+                        correction.1 += moved.velocity - self.physics_bodies[i].velocity;
+                        correction.2 += 1.0;
+                    }
+                    if touched {
+                        touching.push((a, b));
+                    }
+                }
+                for_each_body(&mut self.physics_bodies, |i, b| {
+                    let (pos, velocity, count) = corrections[i];
+                    if count > 0.0 {
+                        b.pos += pos / count;
+                        b.velocity += velocity / count;
+                    }
+                });
+            }
+        }
+        touching
+    }
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use super::*;
+    use crate::physics::{Solver, Xpbd};
+    use crate::{BORDER_CENTER, DT};
+    use glam::Vec4;
+
+    /// A block of balls squeezed together near the bottom of the border, so plenty of them touch several others at once.
+    fn pile(solver: Solver, contact_solver: ContactSolver) -> Scene {
+        let mut scene = Scene {
+            solver,
+            contact_solver,
+            ..Scene::default()
+        };
+        for x in -3..3 {
+            for y in 0..4 {
+                for z in -3..3 {
+                    let jitter = Vec3::new(y as f32 * 0.003, 0.0, x as f32 * 0.002);
+                    let pos = BORDER_CENTER + Vec3::new(x as f32, y as f32 - 7.0, z as f32) * 0.09 + jitter;
+                    let ball = scene.add_ball(0.04, pos, Vec4::ONE);
+                    scene.physics_bodies[ball].velocity = Vec3::new(-x as f32, 0.0, -z as f32) * 0.5;
+                }
+            }
+        }
+        scene
+    }
+
+    fn run_on_threads(threads: usize, solver: Solver, contact_solver: ContactSolver) -> Vec<(Vec3, Vec3)> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| {
+            let mut scene = pile(solver, contact_solver);
+            let mut contacts = 0;
+            for _ in 0..300 {
+                scene.update_physics(DT);
+                contacts += scene.contacts;
+            }
+            assert!(contacts > 300, "only {contacts} contacts, not much of a test");
+            scene.physics_bodies.iter().map(|b| (b.pos, b.velocity)).collect()
+        })
+    }
+
+    #[test]
+    fn same_result_on_any_number_of_threads() {
+        // XPBD doesn't use `contact_solver`, so it only needs checking once
+        let setups = [
+            (Solver::Impulse, ContactSolver::Colored),
+            (Solver::Impulse, ContactSolver::Jacobi),
+            (Solver::Xpbd(Xpbd::default()), ContactSolver::default()),
+        ];
+        for (solver, contact_solver) in setups {
+            let single = run_on_threads(1, solver.clone(), contact_solver);
+            for threads in [2, 4, 7] {
+                assert!(
+                    single == run_on_threads(threads, solver.clone(), contact_solver),
+                    "{solver:?} with {contact_solver:?} differs on {threads} threads"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn colors_never_share_a_body() {
+        let pairs = [(0, 1), (1, 2), (2, 3), (0, 3), (4, 5), (1, 4), (0, 2)];
+        let colors = color(&pairs, 6);
+        assert_eq!(colors.iter().map(Vec::len).sum::<usize>(), pairs.len());
+        for color in &colors {
+            let mut bodies = color.iter().flat_map(|&(a, b)| [a, b]).collect::<Vec<_>>();
+            bodies.sort_unstable();
+            bodies.dedup();
+            assert_eq!(bodies.len(), 2 * color.len());
+        }
+    }
+}
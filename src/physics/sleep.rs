@@ -0,0 +1,195 @@
+use super::Scene;
+use glam::Vec3;
+
+/// Bodies slower than this, in metres per second, count as resting.
+const SLEEP_SPEED: f32 = 0.05;
+
+/// Seconds a body has to stay resting before it can fall asleep.
+const SLEEP_DELAY: f32 = 0.5;
+
+/// Extra distance at which a sleeping body still counts as touching another one, so a pile that settled with small
+/// gaps between its balls still wakes as a whole.
+const TOUCH_SLOP: f32 = 0.01;
+
+/// Disjoint sets over body indices, merged along contacts and constraints to find islands of bodies that rest on
+/// each other.
+struct Islands {
+    parent: Vec<usize>,
+}
+
+impl Islands {
+    fn new(count: usize) -> Self {
+        Self {
+            parent: (0..count).collect(),
+        }
+    }
+
+    fn root(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        self.parent[a] = b;
+    }
+}
+
+impl Scene {
+    /// Wakes every sleeping body, apart from quarantined ones.
+    pub fn wake_all(&mut self) {
+        for b in &mut self.physics_bodies {
+            b.asleep = b.quarantined;
+            b.still_time = 0.0;
+        }
+    }
+
+    /// Wakes sleeping bodies that were grabbed or given a push since the last step, along with everything resting on
+    /// them. Pushes too small to wake a body are dropped, since sleeping bodies don't move.
+    pub(super) fn wake_disturbed(&mut self) {
+        if !self.allow_sleep {
+            self.wake_all();
+            return;
+        }
+        let grabbed = self.grab.as_ref().map(|g| g.body);
+        for i in 0..self.physics_bodies.len() {
+            let b = &mut self.physics_bodies[i];
+            if !b.asleep {
+                continue;
+            }
+            if !b.quarantined && (b.velocity.length() > SLEEP_SPEED || grabbed == Some(i)) {
+                self.wake(i);
+            } else {
+                b.velocity = Vec3::ZERO;
+            }
+        }
+    }
+
+    /// Wakes `body` and every sleeping body touching or connected to it, since they were all resting on each other.
+    pub fn wake(&mut self, body: usize) {
+        let mut stack = vec![body];
+        while let Some(i) = stack.pop() {
+            let b = &mut self.physics_bodies[i];
+            if !b.asleep || b.quarantined {
+                continue;
+            }
+            b.asleep = false;
+            b.still_time = 0.0;
+
+            let b = &self.physics_bodies[i];
+            stack.extend(self.physics_bodies.iter().enumerate().filter_map(|(j, other)| {
+                let touching = b.pos.distance(other.pos) < b.radius + other.radius + TOUCH_SLOP;
+                (other.asleep && touching).then_some(j)
+            }));
+            for constraint in &self.constraints {
+                if constraint.bodies().any(|j| j == i) {
+                    stack.extend(constraint.bodies());
+                }
+            }
+        }
+    }
+
+    /// Counts how long every body has been resting, then puts to sleep each island of touching or connected bodies
+    /// that has rested long enough as a whole. An island with one body still moving stays awake, so nothing falls
+    /// asleep propped up against a body that's about to move.
+    pub(super) fn update_sleep(&mut self, dt: f32, touching: &[(usize, usize)]) {
+        if !self.allow_sleep {
+            return;
+        }
+        let grabbed = self.grab.as_ref().map(|g| g.body);
+        for (i, b) in self.physics_bodies.iter_mut().enumerate() {
+            if b.velocity.length() < SLEEP_SPEED && grabbed != Some(i) {
+                b.still_time += dt;
+            } else {
+                b.still_time = 0.0;
+            }
+        }
+
+        let mut islands = Islands::new(self.physics_bodies.len());
+        for &(a, b) in touching {
+            islands.join(a, b);
+        }
+        for constraint in &self.constraints {
+            let mut bodies = constraint.bodies();
+            if let (Some(a), Some(b)) = (bodies.next(), bodies.next()) {
+                islands.join(a, b);
+            }
+        }
+
+        let mut restless = vec![false; self.physics_bodies.len()];
+        for (i, b) in self.physics_bodies.iter().enumerate() {
+            if !b.asleep && b.still_time < SLEEP_DELAY {
+                restless[islands.root(i)] = true;
+            }
+        }
+        for (i, b) in self.physics_bodies.iter_mut().enumerate() {
+            if !b.asleep && !restless[islands.root(i)] {
+                b.asleep = true;
+                b.velocity = Vec3::ZERO;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BORDER_CENTER, BORDER_RADIUS, DT};
+    use glam::Vec4;
+
+    fn run(scene: &mut Scene, seconds: f32) {
+        for _ in 0..(seconds / DT) as usize {
+            scene.update_physics(DT);
+        }
+    }
+
+    /// A short column of balls resting on the bottom of the border.
+    fn stack() -> Scene {
+        let mut scene = Scene::default();
+        for i in 0..3 {
+            let height = -BORDER_RADIUS + 0.05 + i as f32 * 0.1;
+            scene.add_ball(0.05, BORDER_CENTER + Vec3::Y * height, Vec4::ONE);
+        }
+        scene
+    }
+
+    #[test]
+    fn resting_bodies_fall_asleep() {
+        let mut scene = stack();
+        run(&mut scene, 2.0);
+        assert!(scene.physics_bodies.iter().all(|b| b.asleep));
+
+        let resting = scene.physics_bodies.iter().map(|b| b.pos).collect::<Vec<_>>();
+        run(&mut scene, 1.0);
+        for (b, pos) in scene.physics_bodies.iter().zip(resting) {
+            assert_eq!(b.pos, pos);
+            assert_eq!(b.velocity, Vec3::ZERO);
+        }
+    }
+
+    #[test]
+    fn push_wakes_whole_island() {
+        let mut scene = stack();
+        run(&mut scene, 2.0);
+        scene.physics_bodies[0].velocity = Vec3::X;
+        scene.update_physics(DT);
+        assert!(scene.physics_bodies.iter().all(|b| !b.asleep));
+    }
+
+    #[test]
+    fn falling_body_wakes_what_it_lands_on() {
+        let mut scene = stack();
+        run(&mut scene, 2.0);
+        let ball = scene.add_ball(0.05, BORDER_CENTER + Vec3::Y * 0.2, Vec4::ONE);
+        for _ in 0..(1.0 / DT) as usize {
+            scene.update_physics(DT);
+            if !scene.physics_bodies[2].asleep {
+                return;
+            }
+        }
+        panic!("ball {ball} landed without waking the stack");
+    }
+}
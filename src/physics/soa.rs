@@ -0,0 +1,342 @@
+use super::grid::SpatialGrid;
+use super::PhysicsBody;
+use crate::{BORDER_CENTER, BORDER_RADIUS};
+use glam::Vec3;
+
+/// Bodies stored as structure of arrays, with every component in its own contiguous array. Kernels that touch every
+/// body run the same arithmetic over each array in turn, which the compiler turns into SIMD instructions, instead
+/// of loading and storing one `Vec3` at a time like `Vec<PhysicsBody>` does.
+///
+/// Only the `layout` benchmark uses this, to measure what the layout alone is worth. `Scene` keeps its bodies in a
+/// `Vec<PhysicsBody>`.
+#[derive(Clone, Debug, Default)]
+pub struct BodyStore {
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub z: Vec<f32>,
+    pub vx: Vec<f32>,
+    pub vy: Vec<f32>,
+    pub vz: Vec<f32>,
+    pub radius: Vec<f32>,
+    pub inverse_mass: Vec<f32>,
+    /// Scratch space for `collide_pairs`, kept between calls so it isn't reallocated every time.
+    contacts: Contacts,
+}
+
+/// Contact normal and overlap of every pair passed to `BodyStore::collide_pairs`, in the same order. The overlap is
+/// negative for pairs that aren't touching.
+#[derive(Clone, Debug, Default)]
+struct Contacts {
+    nx: Vec<f32>,
+    ny: Vec<f32>,
+    nz: Vec<f32>,
+    overlap: Vec<f32>,
+}
+
+impl BodyStore {
+    pub fn from_bodies(bodies: &[PhysicsBody]) -> Self {
+        Self {
+            x: bodies.iter().map(|b| b.pos.x).collect(),
+            y: bodies.iter().map(|b| b.pos.y).collect(),
+            z: bodies.iter().map(|b| b.pos.z).collect(),
+            vx: bodies.iter().map(|b| b.velocity.x).collect(),
+            vy: bodies.iter().map(|b| b.velocity.y).collect(),
+            vz: bodies.iter().map(|b| b.velocity.z).collect(),
+            radius: bodies.iter().map(|b| b.radius).collect(),
+            inverse_mass: bodies.iter().map(PhysicsBody::inverse_mass).collect(),
+            contacts: Contacts::default(),
+        }
+    }
+
+    /// Copies positions and velocities back into `bodies`, which must be the ones the store was made from.
+    pub fn write_to(&self, bodies: &mut [PhysicsBody]) {
+        for (i, b) in bodies.iter_mut().enumerate() {
+            b.pos = self.pos(i);
+            b.velocity = self.velocity(i);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    pub fn pos(&self, i: usize) -> Vec3 {
+        Vec3::new(self.x[i], self.y[i], self.z[i])
+    }
+
+    pub fn velocity(&self, i: usize) -> Vec3 {
+        Vec3::new(self.vx[i], self.vy[i], self.vz[i])
+    }
+
+    /// Accelerates every body by `acceleration` and moves it by its new velocity, like the first half of an impulse
+    /// solver step.
+    pub fn integrate(&mut self, acceleration: Vec3, dt: f32) {
+        let axes = [
+            (&mut self.x, &mut self.vx, acceleration.x),
+            (&mut self.y, &mut self.vy, acceleration.y),
+            (&mut self.z, &mut self.vz, acceleration.z),
+        ];
+        for (pos, velocity, acceleration) in axes {
+            for (p, v) in pos.iter_mut().zip(velocity.iter_mut()) {
+                *v += acceleration * dt;
+                *p += *v * dt;
+            }
+        }
+    }
+
+    /// Same as `PhysicsBody::keep_within_border` on every body. Finding the bodies outside is one vectorized pass over
+    /// all of them, and only those few are then corrected one at a time.
+    pub fn keep_within_border(&mut self) {
+        let outside = (self.x.iter().zip(&self.y).zip(&self.z).zip(&self.radius))
+            .map(|(((x, y), z), radius)| {
+                let (dx, dy, dz) = (x - BORDER_CENTER.x, y - BORDER_CENTER.y, z - BORDER_CENTER.z);
+                (dx * dx + dy * dy + dz * dz).sqrt() + radius > BORDER_RADIUS
+            })
+            .collect::<Vec<_>>();
+
+        for i in (0..self.len()).filter(|&i| outside[i]) {
+            let dir = (self.pos(i) - BORDER_CENTER).normalize_or(Vec3::NEG_Y);
+            let pos = BORDER_CENTER + dir * (BORDER_RADIUS - self.radius[i]);
+            let velocity = self.velocity(i);
+            // Elasticity of 0.95, like the border of the impulse solver
+            let velocity = (velocity - 2.0 * velocity.dot(dir) * dir) * 0.95;
+            [self.x[i], self.y[i], self.z[i]] = pos.to_array();
+            [self.vx[i], self.vy[i], self.vz[i]] = velocity.to_array();
+        }
+    }
+
+    /// Pairs of bodies less than `margin` apart, found the same way the scene finds them.
+    pub fn nearby_pairs(&self, margin: f32) -> Vec<(usize, usize)> {
+        let Some(max_radius) = self.radius.iter().copied().reduce(f32::max) else {
+            return Vec::new();
+        };
+        let grid = SpatialGrid::new(2.0 * max_radius + margin, (0..self.len()).map(|i| (i, self.pos(i))));
+        grid.pairs(|a, b| self.pos(a).distance(self.pos(b)) < self.radius[a] + self.radius[b] + margin)
+    }
+
+    /// `PhysicsBody::collide_with` on every pair, in order, except that every pair's normal and overlap come from
+    /// where the bodies were before any of them moved. Returns how many pairs were touching.
+    ///
+    /// Finding the contacts is one pass with no branches, filling an array per component. Only resolving them, where
+    /// each pair depends on the velocities the pairs before it left, is done one pair at a time.
+    pub fn collide_pairs(&mut self, pairs: &[(usize, usize)]) -> usize {
+        let mut contacts = std::mem::take(&mut self.contacts);
+        self.find_contacts(pairs, &mut contacts);
+        let touching = self.resolve_contacts(pairs, &contacts);
+        self.contacts = contacts;
+        touching
+    }
+
+    fn find_contacts(&self, pairs: &[(usize, usize)], contacts: &mut Contacts) {
+        for component in [
+            &mut contacts.nx,
+            &mut contacts.ny,
+            &mut contacts.nz,
+            &mut contacts.overlap,
+        ] {
+            component.clear();
+            component.resize(pairs.len(), 0.0);
+        }
+        let out = (contacts.nx.iter_mut().zip(&mut contacts.ny))
+            .zip(contacts.nz.iter_mut().zip(&mut contacts.overlap))
+            .zip(pairs);
+        for (((nx, ny), (nz, overlap)), &(a, b)) in out {
+            let (dx, dy, dz) = (self.x[b] - self.x[a], self.y[b] - self.y[a], self.z[b] - self.z[a]);
+            let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+            // Pushed apart vertically when the centers are the same, like `collide_with` does. Both sides of these
+            // are cheap, so they compile to selects rather than jumps.
+            let coincident = distance == 0.0;
+            let scale = if coincident { 0.0 } else { 1.0 / distance };
+            *nx = dx * scale;
+            *ny = if coincident { 1.0 } else { dy * scale };
+            *nz = dz * scale;
+            *overlap = self.radius[a] + self.radius[b] - distance;
+        }
+    }
+
+    fn resolve_contacts(&mut self, pairs: &[(usize, usize)], contacts: &Contacts) -> usize {
+        let mut touching = 0;
+        for (k, &(a, b)) in pairs.iter().enumerate() {
+            let overlap = contacts.overlap[k];
+            if overlap <= 0.0 {
+                continue;
+            }
+            touching += 1;
+            let (inverse_mass_a, inverse_mass_b) = (self.inverse_mass[a], self.inverse_mass[b]);
+            let inverse_mass = inverse_mass_a + inverse_mass_b;
+            if inverse_mass == 0.0 {
+                continue;
+            }
+
+            let (nx, ny, nz) = (contacts.nx[k], contacts.ny[k], contacts.nz[k]);
+            let along =
+                (self.vx[b] - self.vx[a]) * nx + (self.vy[b] - self.vy[a]) * ny + (self.vz[b] - self.vz[a]) * nz;
+            if along > 0.0 {
+                continue;
+            }
+
+            // 95% elastic collision
+            let impulse = -(1.0 + 0.95) * along / inverse_mass;
+            self.vx[a] -= impulse * nx * inverse_mass_a;
+            self.vy[a] -= impulse * ny * inverse_mass_a;
+            self.vz[a] -= impulse * nz * inverse_mass_a;
+            self.vx[b] += impulse * nx * inverse_mass_b;
+            self.vy[b] += impulse * ny * inverse_mass_b;
+            self.vz[b] += impulse * nz * inverse_mass_b;
+
+            let separation = overlap / inverse_mass;
+            self.x[a] -= nx * separation * inverse_mass_a;
+            self.y[a] -= ny * separation * inverse_mass_a;
+            self.z[a] -= nz * separation * inverse_mass_a;
+            self.x[b] += nx * separation * inverse_mass_b;
+            self.y[b] += ny * separation * inverse_mass_b;
+            self.z[b] += nz * separation * inverse_mass_b;
+        }
+        touching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::GRAVITY;
+    use crate::DT;
+
+    /// A block of balls thrown in different directions, so they hit each other and the border.
+    fn bodies() -> Vec<PhysicsBody> {
+        let mut bodies = Vec::new();
+        for x in -4..4 {
+            for y in -4..4 {
+                for z in -4..4 {
+                    let mut body = PhysicsBody::new(Vec3::new(x as f32, y as f32, z as f32) * 0.1, 0.04);
+                    body.velocity = Vec3::new(-y as f32, z as f32, x as f32) * 0.7;
+                    body.mass = 1.0 + (x + 4) as f32 * 0.25;
+                    bodies.push(body);
+                }
+            }
+        }
+        bodies
+    }
+
+    #[test]
+    fn integration_and_border_match_array_of_structs() {
+        let mut bodies = bodies();
+        let mut store = BodyStore::from_bodies(&bodies);
+
+        for _ in 0..100 {
+            for b in &mut bodies {
+                b.velocity += GRAVITY * DT;
+                b.pos += b.velocity * DT;
+                b.keep_within_border();
+            }
+            store.integrate(GRAVITY, DT);
+            store.keep_within_border();
+
+            for (i, b) in bodies.iter().enumerate() {
+                assert!(
+                    store.pos(i).distance(b.pos) < 1e-5,
+                    "body {i} at {} not {}",
+                    store.pos(i),
+                    b.pos
+                );
+                assert!(store.velocity(i).distance(b.velocity) < 1e-4);
+            }
+        }
+
+        let mut written = self::bodies();
+        store.write_to(&mut written);
+        for (i, b) in written.iter().enumerate() {
+            assert_eq!((b.pos, b.velocity), (store.pos(i), store.velocity(i)));
+        }
+    }
+
+    #[test]
+    fn separate_contacts_match_collide_with() {
+        // Pairs far from each other, so no body is in more than one contact: head on, glancing, already separating,
+        // on the same spot, against an immovable body and not touching at all
+        let setups = [
+            (Vec3::X * 0.07, Vec3::X, Vec3::NEG_X, 1.0),
+            (Vec3::new(0.05, 0.05, 0.0), Vec3::X, Vec3::Z, 2.0),
+            (Vec3::X * 0.07, Vec3::NEG_X, Vec3::X, 1.0),
+            (Vec3::ZERO, Vec3::Y, Vec3::ZERO, 1.0),
+            (Vec3::Z * 0.06, Vec3::Z, Vec3::ZERO, f32::INFINITY),
+            (Vec3::X * 0.09, Vec3::X, Vec3::NEG_X, 1.0),
+        ];
+        let mut bodies = Vec::new();
+        for (k, (offset, velocity_a, velocity_b, mass_b)) in setups.into_iter().enumerate() {
+            let center = Vec3::X * k as f32 * 0.3;
+            let mut a = PhysicsBody::new(center, 0.04);
+            a.velocity = velocity_a;
+            let mut b = PhysicsBody::new(center + offset, 0.04);
+            b.velocity = velocity_b;
+            b.mass = mass_b;
+            bodies.extend([a, b]);
+        }
+        let pairs = (0..setups.len()).map(|k| (2 * k, 2 * k + 1)).collect::<Vec<_>>();
+        let mut store = BodyStore::from_bodies(&bodies);
+
+        let touching = pairs
+            .iter()
+            .filter(|&&(a, b)| {
+                let (first, rest) = bodies.split_at_mut(b);
+                first[a].collide_with(&mut rest[0])
+            })
+            .count();
+        assert_eq!(store.collide_pairs(&pairs), touching);
+        assert_eq!(touching, setups.len() - 1);
+        for (i, b) in bodies.iter().enumerate() {
+            assert_eq!((store.pos(i), store.velocity(i)), (b.pos, b.velocity), "body {i}");
+        }
+    }
+
+    /// Runs `passes` of contact solving on the block squeezed together so every neighbour overlaps, returning the
+    /// store before and after.
+    fn squeezed(passes: usize, moving: bool) -> (BodyStore, BodyStore) {
+        let mut store = BodyStore::from_bodies(&bodies());
+        for x in [&mut store.x, &mut store.y, &mut store.z] {
+            x.iter_mut().for_each(|x| *x *= 0.7);
+        }
+        if !moving {
+            for v in [&mut store.vx, &mut store.vy, &mut store.vz] {
+                v.fill(0.0);
+            }
+        }
+        let start = store.clone();
+        for _ in 0..passes {
+            let pairs = store.nearby_pairs(0.0);
+            store.collide_pairs(&pairs);
+        }
+        (start, store)
+    }
+
+    #[test]
+    fn crowded_contacts_keep_momentum() {
+        let momentum = |store: &BodyStore| {
+            (0..store.len())
+                .map(|i| store.velocity(i) / store.inverse_mass[i])
+                .sum::<Vec3>()
+        };
+        let (start, end) = squeezed(20, true);
+        assert!(momentum(&start).length() > 1.0);
+        assert!(momentum(&end).distance(momentum(&start)) < 1e-2);
+    }
+
+    #[test]
+    fn crowded_contacts_push_apart() {
+        let total_overlap = |store: &BodyStore| {
+            let pair_overlap =
+                |&(a, b): &(usize, usize)| store.radius[a] + store.radius[b] - store.pos(a).distance(store.pos(b));
+            store.nearby_pairs(0.0).iter().map(pair_overlap).sum::<f32>()
+        };
+        let (start, end) = squeezed(50, false);
+        let (start_overlap, overlap) = (total_overlap(&start), total_overlap(&end));
+        assert!(
+            overlap < start_overlap / 4.0,
+            "overlap only went from {start_overlap} to {overlap}"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use super::Scene;
+use glam::Vec3;
+use std::fmt;
+
+/// What a scene does with bodies whose position or velocity stopped being finite during a step. Left alone, one NaN
+/// spreads to everything that touches it within a few steps.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// Only report them.
+    Report,
+    /// Put them back where they were at the start of the step, standing still.
+    #[default]
+    Reset,
+    /// Put them back like `Reset`, then freeze them for good. They stay in the scene like a body that's asleep and
+    /// never wakes up.
+    Quarantine,
+    /// Panic with a dump of every body, to track down where the first NaN came from.
+    Strict,
+}
+
+/// A body found with a non-finite position or velocity after a step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NonFiniteBody {
+    pub body: usize,
+    /// Step the body went bad on, counting from the first step as 0.
+    pub step: u64,
+    pub pos: Vec3,
+    pub velocity: Vec3,
+}
+
+impl fmt::Display for NonFiniteBody {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "body {} went non-finite on step {}, at {} moving at {}",
+            self.body, self.step, self.pos, self.velocity
+        )
+    }
+}
+
+impl Scene {
+    /// Finds bodies that went non-finite during the step, reports them and deals with them according to
+    /// `non_finite_policy`. `start` holds every body's position from the start of the step. Bodies that were already
+    /// non-finite then, left alone by `Report`, keep the step they went bad on and aren't reported again.
+    pub(super) fn validate(&mut self, start: &[Vec3]) {
+        let previous = std::mem::take(&mut self.non_finite_bodies);
+        self.non_finite_bodies = self
+            .physics_bodies
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !(b.pos.is_finite() && b.velocity.is_finite()))
+            .map(|(body, b)| NonFiniteBody {
+                body,
+                step: previous
+                    .iter()
+                    .find(|p| p.body == body && !start[body].is_finite())
+                    .map_or(self.steps, |p| p.step),
+                pos: b.pos,
+                velocity: b.velocity,
+            })
+            .collect();
+        if self.non_finite_bodies.is_empty() {
+            return;
+        }
+
+        let report = self
+            .non_finite_bodies
+            .iter()
+            .filter(|invalid| invalid.step == self.steps)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.non_finite_policy == NonFinitePolicy::Strict {
+            panic!("{report}\n{}", self.dump());
+        }
+        if !report.is_empty() {
+            eprintln!("{report}");
+        }
+
+        if self.non_finite_policy == NonFinitePolicy::Report {
+            return;
+        }
+        for invalid in &self.non_finite_bodies {
+            let b = &mut self.physics_bodies[invalid.body];
+            let previous = start[invalid.body];
+            b.pos = if previous.is_finite() {
+                previous
+            } else {
+                self.container.center
+            };
+            b.velocity = Vec3::ZERO;
+            if self.non_finite_policy == NonFinitePolicy::Quarantine {
+                b.quarantined = true;
+                b.asleep = true;
+            }
+        }
+    }
+
+    /// Every body's state, one per line, along with the step and time.
+    pub fn dump(&self) -> String {
+        let mut dump = format!("step {}, time {}", self.steps, self.time);
+        for (i, b) in self.physics_bodies.iter().enumerate() {
+            dump += &format!("\n{i}: {b:?}");
+        }
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{Solver, Xpbd};
+    use crate::DT;
+    use glam::Vec4;
+
+    /// Two balls with the second about to go bad, next to a third that's nowhere near them.
+    fn scene(non_finite_policy: NonFinitePolicy) -> Scene {
+        let mut scene = Scene {
+            non_finite_policy,
+            ..Scene::default()
+        };
+        scene.add_ball(0.05, Vec3::new(0.0, -0.5, 0.0), Vec4::ONE);
+        scene.add_ball(0.05, Vec3::new(0.0, -0.42, 0.0), Vec4::ONE);
+        scene.add_ball(0.05, Vec3::new(0.5, 0.0, 0.0), Vec4::ONE);
+        scene.update_physics(DT);
+        scene.physics_bodies[1].velocity = Vec3::new(f32::NAN, 0.0, 0.0);
+        scene
+    }
+
+    #[test]
+    fn reports_body_and_step() {
+        let mut scene = scene(NonFinitePolicy::Report);
+        scene.update_physics(DT);
+        assert_eq!(scene.non_finite_bodies.len(), 1);
+        assert_eq!(
+            (scene.non_finite_bodies[0].body, scene.non_finite_bodies[0].step),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn reported_body_keeps_the_step_it_went_bad_on() {
+        let mut scene = scene(NonFinitePolicy::Report);
+        for _ in 0..10 {
+            scene.update_physics(DT);
+        }
+        let bad = scene
+            .non_finite_bodies
+            .iter()
+            .find(|invalid| invalid.body == 1)
+            .expect("body 1 is still non-finite");
+        assert_eq!(bad.step, 1);
+        assert!(!bad.pos.is_finite());
+    }
+
+    #[test]
+    fn reset_body_going_bad_again_is_reported_again() {
+        let mut scene = scene(NonFinitePolicy::Reset);
+        scene.update_physics(DT);
+        scene.physics_bodies[1].velocity = Vec3::new(f32::NAN, 0.0, 0.0);
+        scene.update_physics(DT);
+        assert_eq!(scene.non_finite_bodies.len(), 1);
+        assert_eq!(scene.non_finite_bodies[0].step, 2);
+        assert!(scene.physics_bodies[1].pos.is_finite());
+    }
+
+    #[test]
+    fn reset_stops_nan_spreading() {
+        let mut scene = scene(NonFinitePolicy::Reset);
+        let start = scene.physics_bodies[1].pos;
+        scene.update_physics(DT);
+        assert_eq!(scene.non_finite_bodies.len(), 1);
+        assert_eq!(scene.physics_bodies[1].pos, start);
+
+        for _ in 0..100 {
+            scene.update_physics(DT);
+            assert!(scene.non_finite_bodies.is_empty());
+        }
+        assert!(scene
+            .physics_bodies
+            .iter()
+            .all(|b| b.pos.is_finite() && b.velocity.is_finite()));
+        assert!(!scene.physics_bodies[1].asleep, "reset bodies carry on moving");
+    }
+
+    #[test]
+    fn quarantined_body_stays_frozen() {
+        let mut scene = scene(NonFinitePolicy::Quarantine);
+        scene.update_physics(DT);
+        let frozen = scene.physics_bodies[1].pos;
+        scene.wake_all();
+        for _ in 0..100 {
+            scene.update_physics(DT);
+        }
+        assert!(scene.physics_bodies[1].asleep);
+        assert_eq!(scene.physics_bodies[1].pos, frozen);
+        assert!(scene
+            .physics_bodies
+            .iter()
+            .all(|b| b.pos.is_finite() && b.velocity.is_finite()));
+    }
+
+    #[test]
+    fn quarantined_body_is_not_pushed_by_contacts() {
+        let mut scene = scene(NonFinitePolicy::Quarantine);
+        scene.update_physics(DT);
+        let frozen = scene.physics_bodies[1].pos;
+        scene.gravity = Vec3::ZERO;
+        let b = &mut scene.physics_bodies[2];
+        b.pos = frozen + Vec3::X * 0.2;
+        b.velocity = Vec3::NEG_X * 2.0;
+        for _ in 0..200 {
+            scene.update_physics(DT);
+        }
+        assert_eq!(scene.physics_bodies[1].pos, frozen);
+        assert_eq!(scene.physics_bodies[1].velocity, Vec3::ZERO);
+        assert!(
+            scene.physics_bodies[2].velocity.x > 0.0,
+            "ball never bounced off the frozen one"
+        );
+    }
+
+    #[test]
+    fn infinite_position_does_not_break_contact_search() {
+        let mut scene = scene(NonFinitePolicy::Reset);
+        scene.solver = Solver::Xpbd(Xpbd::default());
+        // Lands in the last cell of the contact grid, whose neighbours would be past the end of `i32`
+        scene.physics_bodies[1].pos = Vec3::INFINITY;
+        scene.physics_bodies[1].velocity = Vec3::ZERO;
+        scene.update_physics(DT);
+        assert_eq!(scene.non_finite_bodies.len(), 1);
+        assert!(scene
+            .physics_bodies
+            .iter()
+            .all(|b| b.pos.is_finite() && b.velocity.is_finite()));
+    }
+
+    #[test]
+    #[should_panic(expected = "body 1 went non-finite on step 1")]
+    fn strict_mode_panics() {
+        scene(NonFinitePolicy::Strict).update_physics(DT);
+    }
+}
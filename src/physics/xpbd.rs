@@ -0,0 +1,289 @@
+use super::parallel::for_each_body;
+use super::Scene;
+use crate::BORDER_RADIUS;
+use glam::Vec3;
+
+/// Settings for the extended position based dynamics solver.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Xpbd {
+    /// Number of smaller steps each physics step is split into. More substeps make stiff contacts and long chains
+    /// converge better than extra iterations would.
+    pub substeps: usize,
+    /// Inverse stiffness of contacts, in metres per newton. Zero makes contacts perfectly hard.
+    pub contact_compliance: f32,
+    /// Inverse stiffness of distance and rope constraints. Springs use the inverse of their own stiffness instead.
+    pub constraint_compliance: f32,
+    /// Fraction of the approach speed kept after a bounce.
+    pub restitution: f32,
+    /// Coefficient of dynamic friction between touching bodies.
+    pub friction: f32,
+}
+
+impl Default for Xpbd {
+    fn default() -> Self {
+        Self {
+            substeps: 8,
+            contact_compliance: 0.0,
+            constraint_compliance: 0.0,
+            restitution: 0.95,
+            friction: 0.3,
+        }
+    }
+}
+
+/// Contact found while projecting positions, kept around for the velocity pass.
+struct Contact {
+    a: usize,
+    /// The other body, or `None` for the border.
+    b: Option<usize>,
+    /// Unit vector from `a` towards `b`, or out through the border.
+    normal: Vec3,
+    /// Positional impulse the contact applied this substep.
+    lambda: f32,
+    /// Speed at which the bodies were separating at the start of the substep. Negative while approaching.
+    separating_speed: f32,
+}
+
+impl Scene {
+    /// Advances the scene by `dt` with XPBD: every substep moves bodies by their velocity, projects positions out of
+    /// contacts and back onto constraints, then takes the velocity from how far each body actually moved.
+    /// Restitution and friction are applied to the new velocities afterwards, since positions alone can't express them.
+    /// Returns the pairs of bodies found touching.
+    pub(super) fn step_xpbd(&mut self, dt: f32, accelerations: &[Vec3], settings: &Xpbd) -> Vec<(usize, usize)> {
+        let substeps = settings.substeps.max(1);
+        let h = dt / substeps as f32;
+        let pairs = self.candidate_pairs(dt, accelerations);
+        let mut touching = Vec::new();
+
+        for substep in 0..substeps {
+            let previous = self.physics_bodies.iter().map(|b| b.pos).collect::<Vec<_>>();
+            for_each_body(&mut self.physics_bodies, |i, b| {
+                if !b.asleep {
+                    b.velocity += accelerations[i] * h;
+                    b.pos += b.velocity * h;
+                }
+            });
+            self.sweep_fast_bodies(&previous);
+
+            let mut contacts = Vec::new();
+            for a in 0..self.physics_bodies.len() {
+                if self.physics_bodies[a].asleep {
+                    continue;
+                }
+                if let Some(contact) = self.solve_border_contact(a, h, settings.contact_compliance) {
+                    contacts.push(contact);
+                }
+            }
+            for &(a, b) in &pairs {
+                if let Some(contact) = self.solve_contact(a, b, h, settings.contact_compliance) {
+                    if substep == 0 {
+                        touching.push((a, b));
+                    }
+                    contacts.push(contact);
+                }
+            }
+
+            for constraint in &self.constraints {
+                if constraint.is_asleep(&self.physics_bodies) {
+                    continue;
+                }
+                constraint.solve_xpbd(&mut self.physics_bodies, &previous, h, settings.constraint_compliance);
+            }
+
+            for_each_body(&mut self.physics_bodies, |i, b| b.velocity = (b.pos - previous[i]) / h);
+
+            for contact in &contacts {
+                self.apply_contact_velocity(contact, accelerations, h, settings);
+            }
+        }
+        touching
+    }
+
+    /// Pairs of bodies that might touch at some point during the next `dt`. Found once per step so substeps only test
+    /// these instead of searching again.
+    fn candidate_pairs(&self, dt: f32, accelerations: &[Vec3]) -> Vec<(usize, usize)> {
+        // Furthest any body could move towards another this step
+        let max_travel = self
+            .physics_bodies
+            .iter()
+            .zip(accelerations)
+            .map(|(b, a)| (b.velocity + *a * dt).length() * dt)
+            .fold(0.0, f32::max);
+        self.nearby_pairs(2.0 * max_travel)
+    }
+
+    fn solve_contact(&mut self, a: usize, b: usize, h: f32, compliance: f32) -> Option<Contact> {
+        let (body_a, body_b) = (&self.physics_bodies[a], &self.physics_bodies[b]);
+        let offset = body_b.pos - body_a.pos;
+        let gap = offset.length() - (body_a.radius + body_b.radius);
+        if gap >= 0.0 {
+            return None;
+        }
+        let inverse_mass_a = body_a.inverse_mass();
+        let inverse_mass_b = body_b.inverse_mass();
+        if inverse_mass_a + inverse_mass_b == 0.0 {
+            return None;
+        }
+        let normal = offset.normalize_or(Vec3::Y);
+        let separating_speed = (body_b.velocity - body_a.velocity).dot(normal);
+
+        let lambda = -gap / (inverse_mass_a + inverse_mass_b + compliance / (h * h));
+        self.physics_bodies[a].pos -= normal * lambda * inverse_mass_a;
+        self.physics_bodies[b].pos += normal * lambda * inverse_mass_b;
+        Some(Contact {
+            a,
+            b: Some(b),
+            normal,
+            lambda,
+            separating_speed,
+        })
+    }
+
+    fn solve_border_contact(&mut self, a: usize, h: f32, compliance: f32) -> Option<Contact> {
+        let body = &mut self.physics_bodies[a];
+        let offset = body.pos - self.container.center;
+        let gap = BORDER_RADIUS - body.radius - offset.length();
+        if gap >= 0.0 {
+            return None;
+        }
+        let inverse_mass = body.inverse_mass();
+        if inverse_mass == 0.0 {
+            return None;
+        }
+        let normal = offset.normalize_or(Vec3::NEG_Y);
+        let wall_velocity = self
+            .container
+            .wall_velocity(self.container.center + normal * BORDER_RADIUS);
+        let separating_speed = (wall_velocity - body.velocity).dot(normal);
+
+        let lambda = -gap / (inverse_mass + compliance / (h * h));
+        body.pos -= normal * lambda * inverse_mass;
+        Some(Contact {
+            a,
+            b: None,
+            normal,
+            lambda,
+            separating_speed,
+        })
+    }
+
+    /// Bounces and slows down the bodies in `contact`, each in proportion to its inverse mass.
+    fn apply_contact_velocity(&mut self, contact: &Contact, accelerations: &[Vec3], h: f32, settings: &Xpbd) {
+        let bodies = &self.physics_bodies;
+        let (velocity_b, inverse_mass_b, acceleration_b) = match contact.b {
+            Some(b) => (bodies[b].velocity, bodies[b].inverse_mass(), accelerations[b]),
+            // The wall where the body touches it, which moves with the container
+            None => {
+                let point = self.container.center + contact.normal * BORDER_RADIUS;
+                (self.container.wall_velocity(point), 0.0, Vec3::ZERO)
+            }
+        };
+        let a = &bodies[contact.a];
+        let inverse_mass_a = a.inverse_mass();
+        let inverse_mass = inverse_mass_a + inverse_mass_b;
+        if inverse_mass == 0.0 {
+            return;
+        }
+
+        let relative_velocity = velocity_b - a.velocity;
+        let separating_speed = relative_velocity.dot(contact.normal);
+        let mut change = Vec3::ZERO;
+
+        // Bodies resting on each other only approach by what the external forces added this substep. Bouncing those
+        // would keep them hopping forever, so they are stopped instead.
+        let resting_speed = 2.0 * accelerations[contact.a].length().max(acceleration_b.length()) * h;
+        let restitution = if contact.separating_speed.abs() <= resting_speed {
+            0.0
+        } else {
+            settings.restitution
+        };
+        let target_speed = (-restitution * contact.separating_speed).max(0.0);
+        change += contact.normal * (target_speed - separating_speed);
+
+        let tangential = relative_velocity - contact.normal * separating_speed;
+        let friction = (settings.friction * contact.lambda / h).min(tangential.length());
+        change -= tangential.normalize_or_zero() * friction;
+
+        self.physics_bodies[contact.a].velocity -= change * inverse_mass_a / inverse_mass;
+        if let Some(b) = contact.b {
+            self.physics_bodies[b].velocity += change * inverse_mass_b / inverse_mass;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::Solver;
+    use crate::{BORDER_CENTER, DT};
+    use glam::Vec4;
+
+    /// Balls dropped in layers onto the bottom of the border, left to settle before anything is measured.
+    fn settled_pile(allow_sleep: bool) -> Scene {
+        const RADIUS: f32 = 0.05;
+        let mut scene = Scene {
+            solver: Solver::Xpbd(Xpbd::default()),
+            allow_sleep,
+            ..Scene::default()
+        };
+        for layer in 0..3 {
+            for x in -2..=2 {
+                for z in -1..=1 {
+                    let offset = if layer % 2 == 0 { 0.0 } else { RADIUS };
+                    let pos = Vec3::new(
+                        x as f32 * 2.2 * RADIUS + offset,
+                        -0.6 + layer as f32 * 2.2 * RADIUS,
+                        z as f32 * 2.2 * RADIUS,
+                    );
+                    scene.add_ball(RADIUS, BORDER_CENTER + pos, Vec4::ONE);
+                }
+            }
+        }
+        for _ in 0..(5.0 / DT) as usize {
+            scene.update_physics(DT);
+        }
+        scene
+    }
+
+    #[test]
+    fn resting_pile_is_stable() {
+        // Awake throughout, or this would only be measuring bodies frozen in place
+        let mut scene = settled_pile(false);
+        let start = scene.physics_bodies.iter().map(|b| b.pos).collect::<Vec<_>>();
+
+        for step in 0..(10.0 / DT) as usize {
+            scene.update_physics(DT);
+            for (i, b) in scene.physics_bodies.iter().enumerate() {
+                assert!(b.pos.distance(BORDER_CENTER) <= BORDER_RADIUS - b.radius + 1e-4);
+                let drift = b.pos.distance(start[i]);
+                assert!(drift < 0.01, "body {i} drifted {drift} by step {step}");
+            }
+        }
+
+        let fastest = scene
+            .physics_bodies
+            .iter()
+            .map(|b| b.velocity.length())
+            .fold(0.0, f32::max);
+        assert!(fastest < 0.01, "fastest body still moving at {fastest}");
+        for i in 0..scene.physics_bodies.len() {
+            for j in i + 1..scene.physics_bodies.len() {
+                let (a, b) = (&scene.physics_bodies[i], &scene.physics_bodies[j]);
+                let overlap = a.radius + b.radius - a.pos.distance(b.pos);
+                assert!(overlap < 1e-3, "bodies {i} and {j} overlap by {overlap}");
+            }
+        }
+    }
+
+    #[test]
+    fn settled_pile_falls_asleep_where_it_rests() {
+        let mut scene = settled_pile(true);
+        assert!(scene.physics_bodies.iter().all(|b| b.asleep), "pile never fell asleep");
+        let start = scene.physics_bodies.iter().map(|b| b.pos).collect::<Vec<_>>();
+        for _ in 0..1000 {
+            scene.update_physics(DT);
+        }
+        assert!(scene.physics_bodies.iter().all(|b| b.asleep));
+        assert!(scene.physics_bodies.iter().zip(&start).all(|(b, &pos)| b.pos == pos));
+    }
+}
@@ -1,5 +1,5 @@
-use crate::physics::Mesh;
-use crate::Scene;
+use crate::physics::{Mesh, Scene};
+use glam::Vec4;
 use wgpu::util::DeviceExt;
 
 pub struct BufferManager {
@@ -7,6 +7,8 @@ pub struct BufferManager {
     pub static_index_buffer: wgpu::Buffer,
     pub dynamic_vertex_buffer: wgpu::Buffer,
     pub dynamic_index_buffer: wgpu::Buffer,
+    pub line_vertex_buffer: wgpu::Buffer,
+    pub line_vertex_count: u32,
 }
 
 impl BufferManager {
@@ -35,15 +37,24 @@ impl BufferManager {
             usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
         });
 
+        let line_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Line Vertex Buffer"),
+            contents: bytemuck::cast_slice(&scene.constraint_vertices()),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
         Self {
             static_vertex_buffer,
             static_index_buffer,
             dynamic_vertex_buffer,
             dynamic_index_buffer,
+            line_vertex_buffer,
+            line_vertex_count: 0,
         }
     }
 
-    pub fn update_dynamic_buffers(&mut self, queue: &wgpu::Queue, scene: &Scene) {
+    /// Uploads the dynamic meshes, growing the buffers first if balls were added since the last upload.
+    pub fn update_dynamic_buffers(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) {
         let all_vertices = scene
             .dynamic_meshes
             .iter()
@@ -53,17 +64,65 @@ impl BufferManager {
 
         let all_indices: Vec<u32> = scene.dynamic_meshes.iter().flat_map(|m| &m.indices).copied().collect();
 
+        grow_buffer(
+            device,
+            &mut self.dynamic_vertex_buffer,
+            bytemuck::cast_slice(&all_vertices),
+        );
+        grow_buffer(
+            device,
+            &mut self.dynamic_index_buffer,
+            bytemuck::cast_slice(&all_indices),
+        );
+
         queue.write_buffer(&self.dynamic_vertex_buffer, 0, bytemuck::cast_slice(&all_vertices));
         queue.write_buffer(&self.dynamic_index_buffer, 0, bytemuck::cast_slice(&all_indices));
+
+        let line_vertices = scene.constraint_vertices();
+        grow_buffer(
+            device,
+            &mut self.line_vertex_buffer,
+            bytemuck::cast_slice(&line_vertices),
+        );
+        queue.write_buffer(&self.line_vertex_buffer, 0, bytemuck::cast_slice(&line_vertices));
+        self.line_vertex_count = line_vertices.len() as u32;
+    }
+
+    /// Moves the static meshes along with the container. They don't change otherwise, so this is only needed while
+    /// the container moves.
+    pub fn update_static_buffers(&self, queue: &wgpu::Queue, scene: &Scene) {
+        queue.write_buffer(
+            &self.static_vertex_buffer,
+            0,
+            bytemuck::cast_slice(&scene.static_vertices()),
+        );
     }
 }
 
+/// Replaces `buffer` with one twice the size of `contents` when it no longer fits, so spawning balls one at a time
+/// doesn't reallocate on every spawn.
+fn grow_buffer(device: &wgpu::Device, buffer: &mut wgpu::Buffer, contents: &[u8]) {
+    if contents.len() as wgpu::BufferAddress <= buffer.size() {
+        return;
+    }
+    *buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Dynamic Buffer"),
+        size: 2 * contents.len() as wgpu::BufferAddress,
+        usage: buffer.usage(),
+        mapped_at_creation: false,
+    });
+}
+
 pub fn render_objects(
     render_pass: &mut wgpu::RenderPass,
     vertex_buffer: &wgpu::Buffer,
     index_buffer: &wgpu::Buffer,
     meshes: &[Mesh],
 ) {
+    // Buffers for a scene without any of these meshes are empty, and wgpu won't bind an empty slice
+    if meshes.is_empty() {
+        return;
+    }
     render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
     render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
 
@@ -75,3 +134,83 @@ pub fn render_objects(
         );
     }
 }
+
+pub fn render_lines(render_pass: &mut wgpu::RenderPass, vertex_buffer: &wgpu::Buffer, vertex_count: u32) {
+    if vertex_count == 0 {
+        return;
+    }
+    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    render_pass.draw(0..vertex_count, 0..1);
+}
+
+/// Converts an sRGB color, the space colors are usually picked in, to the linear space shading and blending happen
+/// in. Alpha is already linear and is left as is.
+pub fn srgb_to_linear(color: Vec4) -> Vec4 {
+    let channel = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Vec4::new(channel(color.x), channel(color.y), channel(color.z), color.w)
+}
+
+/// Draws the balls and constraint lines of `scene` into a small texture. There's no pipeline, so anything actually
+/// drawn fails validation, which leaves this for checking scenes with nothing to draw.
+#[cfg(test)]
+pub(crate) fn draw_dynamic(device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) {
+    let buffers = BufferManager::new(device, scene);
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations::default(),
+            })],
+            ..Default::default()
+        });
+        render_objects(
+            &mut render_pass,
+            &buffers.dynamic_vertex_buffer,
+            &buffers.dynamic_index_buffer,
+            &scene.dynamic_meshes,
+        );
+        render_lines(&mut render_pass, &buffers.line_vertex_buffer, buffers.line_vertex_count);
+    }
+    queue.submit([encoder.finish()]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu_physics::software_device;
+    use crate::scene_file;
+
+    #[test]
+    fn draws_scene_file_without_balls() {
+        let Some((device, queue)) = software_device() else {
+            eprintln!("skipping: no software adapter");
+            return;
+        };
+        let scene = scene_file::parse("gravity = [0.0, -9.8, 0.0]").unwrap();
+        assert!(scene.dynamic_meshes.is_empty());
+        draw_dynamic(&device, &queue, &scene);
+    }
+}
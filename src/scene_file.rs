@@ -0,0 +1,269 @@
+use crate::demos::bordered_scene;
+use crate::physics::{Container, Mass, MotionPath, Scene, Solver, Xpbd, GRAVITY};
+use crate::DT;
+use glam::{Vec3, Vec4};
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A scene described in TOML, loaded with `--scene`. Every ball goes inside the border, which can move like
+/// `Container` says.
+///
+/// ```toml
+/// solver = "xpbd"
+///
+/// # A drum turning on its axle
+/// [container]
+/// spin = [0.0, 0.0, 1.5]
+/// friction = 0.6
+///
+/// [[balls]]
+/// radius = 0.05
+/// pos = [0.0, 0.3, 0.0]
+/// density = 1000.0
+///
+/// # Immovable, and moved along keyframes instead of simulated
+/// [[balls]]
+/// radius = 0.1
+/// path = { looping = true, keyframes = [
+///     { time = 0.0, pos = [-0.4, -0.5, 0.0] },
+///     { time = 1.0, pos = [0.4, -0.5, 0.0] },
+///     { time = 2.0, pos = [-0.4, -0.5, 0.0] },
+/// ] }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default = "default_gravity")]
+    gravity: Vec3,
+    #[serde(default)]
+    solver: SolverName,
+    #[serde(default)]
+    container: Container,
+    #[serde(default)]
+    balls: Vec<Ball>,
+}
+
+fn default_gravity() -> Vec3 {
+    GRAVITY
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SolverName {
+    #[default]
+    Impulse,
+    Xpbd,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Ball {
+    radius: f32,
+    /// Where the ball starts. Balls following a path start at its beginning instead.
+    pos: Option<Vec3>,
+    #[serde(default = "default_color")]
+    color: Vec4,
+    #[serde(default)]
+    velocity: Vec3,
+    #[serde(default)]
+    charge: f32,
+    /// In kilograms. At most one of `mass`, `density` and `immovable` can be given, and without any the ball
+    /// weighs one kilogram.
+    mass: Option<f32>,
+    density: Option<f32>,
+    #[serde(default)]
+    immovable: bool,
+    /// Keyframes to move the ball along. Such balls are always immovable.
+    path: Option<MotionPath>,
+}
+
+fn default_color() -> Vec4 {
+    Vec4::ONE
+}
+
+/// Why a scene file couldn't be loaded.
+#[derive(Debug)]
+pub enum SceneFileError {
+    Read(PathBuf, std::io::Error),
+    Parse(toml::de::Error),
+    /// The file parsed, but describes something that can't be simulated.
+    Invalid(String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "unable to read {}: {e}", path.display()),
+            Self::Parse(e) => write!(f, "{e}"),
+            Self::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read(_, e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+/// Reads and builds the scene in the file at `path`.
+pub fn load(path: &Path) -> Result<Scene, SceneFileError> {
+    let text = std::fs::read_to_string(path).map_err(|e| SceneFileError::Read(path.to_owned(), e))?;
+    parse(&text)
+}
+
+/// Builds the scene described by `text`.
+pub fn parse(text: &str) -> Result<Scene, SceneFileError> {
+    let file: SceneFile = toml::from_str(text).map_err(SceneFileError::Parse)?;
+    let mut scene = bordered_scene();
+    scene.gravity = file.gravity;
+    scene.solver = match file.solver {
+        SolverName::Impulse => Solver::Impulse,
+        SolverName::Xpbd => Solver::Xpbd(Xpbd::default()),
+    };
+
+    let container = file.container;
+    let invalid = |problem: &str| SceneFileError::Invalid(format!("the container {problem}"));
+    if let Some(path) = &container.path {
+        check_path(path).map_err(invalid)?;
+    }
+    if container.shake.is_some_and(|s| s.frequency < 0.0) {
+        return Err(invalid("can't shake at a negative frequency"));
+    }
+    if !(0.0..=1.0).contains(&container.friction) {
+        return Err(invalid("needs a friction between 0 and 1"));
+    }
+    scene.container = container;
+    scene.container.move_to(scene.time, DT);
+
+    for (i, ball) in file.balls.into_iter().enumerate() {
+        let invalid = |problem: &str| SceneFileError::Invalid(format!("ball {i} {problem}"));
+        if ball.radius <= 0.0 {
+            return Err(invalid("needs a positive radius"));
+        }
+        let mass = match (ball.mass, ball.density, ball.immovable || ball.path.is_some()) {
+            (None, None, false) => Mass::Kilograms(1.0),
+            (Some(mass), None, false) if mass > 0.0 => Mass::Kilograms(mass),
+            (None, Some(density), false) if density > 0.0 => Mass::Density(density),
+            (None, None, true) => Mass::Immovable,
+            (Some(_), None, false) | (None, Some(_), false) => return Err(invalid("needs a positive mass or density")),
+            _ => return Err(invalid("can only have one of mass, density, immovable and path")),
+        };
+
+        let index = match ball.path {
+            Some(path) => {
+                check_path(&path).map_err(invalid)?;
+                if ball.pos.is_some() {
+                    return Err(invalid("has both a pos and a path to follow"));
+                }
+                scene.add_kinematic_ball(ball.radius, ball.color, path)
+            }
+            None => {
+                let pos = ball.pos.ok_or_else(|| invalid("needs a pos or a path"))?;
+                scene.add_ball_with_mass(ball.radius, pos, ball.color, mass)
+            }
+        };
+        let body = &mut scene.physics_bodies[index];
+        body.velocity = ball.velocity;
+        body.charge = ball.charge;
+    }
+    Ok(scene)
+}
+
+/// Checks `path` can be followed, describing the problem if not.
+fn check_path(path: &MotionPath) -> Result<(), &'static str> {
+    if path.keyframes.is_empty() {
+        return Err("has a path with no keyframes");
+    }
+    if path.keyframes.windows(2).any(|pair| pair[0].time > pair[1].time) {
+        return Err("has keyframes out of order");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_scenes_load() {
+        for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes")).unwrap() {
+            let path = entry.unwrap().path();
+            let scene = load(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            assert!(!scene.physics_bodies.is_empty(), "{} is empty", path.display());
+        }
+    }
+
+    #[test]
+    fn builds_balls_and_paths() {
+        let scene = parse(
+            r#"
+            gravity = [0.0, 0.0, 0.0]
+            solver = "xpbd"
+
+            [container]
+            shake = { amplitude = [0.0, 0.02, 0.0], frequency = 5.0 }
+
+            [[balls]]
+            radius = 0.05
+            pos = [0.1, 0.2, 0.3]
+            velocity = [1.0, 0.0, 0.0]
+            density = 1000.0
+
+            [[balls]]
+            radius = 0.1
+            path = { interpolation = "smooth", keyframes = [
+                { time = 0.0, pos = [0.0, -0.5, 0.0] },
+                { time = 1.0, pos = [0.5, -0.5, 0.0] },
+            ] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scene.gravity, Vec3::ZERO);
+        assert!(matches!(scene.solver, Solver::Xpbd(_)));
+        let (ball, paddle) = (&scene.physics_bodies[0], &scene.physics_bodies[1]);
+        assert_eq!((ball.pos, ball.velocity), (Vec3::new(0.1, 0.2, 0.3), Vec3::X));
+        assert_eq!(ball.mass, Mass::Density(1000.0).of_ball(0.05));
+        assert!(paddle.is_immovable());
+        assert_eq!(paddle.pos, Vec3::new(0.0, -0.5, 0.0));
+        assert_eq!(scene.kinematic.len(), 1);
+        assert_eq!(scene.kinematic[0].body, 1);
+        assert_eq!(scene.container.shake.unwrap().frequency, 5.0);
+    }
+
+    #[test]
+    fn rejects_what_cant_be_simulated() {
+        let problems = [
+            ("radius = 0.0\npos = [0, 0, 0]", "positive radius"),
+            ("radius = 0.1", "needs a pos or a path"),
+            (
+                "radius = 0.1\npos = [0, 0, 0]\nmass = 1.0\ndensity = 2.0",
+                "only have one",
+            ),
+            ("radius = 0.1\npos = [0, 0, 0]\nmass = -1.0", "positive mass"),
+            ("radius = 0.1\npath = { keyframes = [] }", "no keyframes"),
+            (
+                "radius = 0.1\npath = { keyframes = [{ time = 1, pos = [0, 0, 0] }, { time = 0, pos = [0, 0, 0] }] }",
+                "out of order",
+            ),
+        ];
+        for (ball, problem) in problems {
+            let error = parse(&format!("[[balls]]\n{ball}")).unwrap_err().to_string();
+            assert!(error.contains(problem), "{ball:?} gave {error:?}");
+        }
+        assert!(matches!(
+            parse("[[balls]]\nradius = 0.1\nspeed = 3"),
+            Err(SceneFileError::Parse(_))
+        ));
+
+        let error = parse("[container]\nfriction = 2.0").unwrap_err().to_string();
+        assert!(error.contains("friction between 0 and 1"), "{error:?}");
+        let error = parse("[container]\npath = { keyframes = [] }").unwrap_err().to_string();
+        assert!(error.contains("container has a path with no keyframes"), "{error:?}");
+    }
+}
@@ -0,0 +1,451 @@
+use crate::physics::{Mass, PhysicsBody, Scene};
+use glam::{Vec3, Vec4};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+
+/// A Rhai script that sets up a scene and runs alongside it, loaded with `--script`. Both hooks are optional:
+///
+/// ```rhai
+/// // Called once on a fresh scene, and again whenever the script is reloaded
+/// fn setup() {
+///     this.launched = 0;
+///     add_fixed_ball(0.1, vec3(0.0, -0.3, 0.0));
+/// }
+///
+/// // Called before every physics step
+/// fn step() {
+///     if steps() % 200 == 0 {
+///         let ball = add_ball(0.03, vec3(0.0, 0.6, 0.0), 0.5);
+///         set_velocity(ball, vec3(0.3, 0.0, 0.0));
+///         this.launched += 1;
+///     }
+///     if this.launched == 20 {
+///         stop("launched every ball");
+///     }
+/// }
+/// ```
+///
+/// `this` is an object map kept from `setup` through every `step`, since script functions can't see variables
+/// outside them. Positions and velocities are `Vec3`s made with `vec3(x, y, z)`, with `x`, `y` and `z` fields,
+/// arithmetic, `length`, `normalize`, `dot` and `distance`. Balls are numbered in the order they were added, and the
+/// scene is reached through these functions:
+///
+/// | Function | Does |
+/// | --- | --- |
+/// | `ball_count()` | Number of balls |
+/// | `add_ball(radius, pos, mass, color)` | Adds a ball and returns its number. `mass` defaults to 1, `color` to white |
+/// | `add_fixed_ball(radius, pos)` | Adds an immovable ball |
+/// | `pos(ball)`, `velocity(ball)`, `mass(ball)`, `radius(ball)` | Reads a ball |
+/// | `set_pos(ball, pos)`, `set_velocity(ball, velocity)` | Moves a ball or changes its velocity |
+/// | `apply_force(ball, force)` | Pushes a ball for the coming step |
+/// | `explode(center, strength, radius)` | Pushes every ball near `center` away from it |
+/// | `gravity()`, `set_gravity(gravity)` | Reads or changes gravity |
+/// | `time()`, `steps()` | Simulated seconds and steps so far |
+/// | `stop(reason)` | Ends the run after this step |
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    host: Rc<RefCell<Host>>,
+    /// The script's own state, bound to `this` in its hooks.
+    this: Dynamic,
+    /// Set once a hook fails, so a broken script is reported once instead of on every step until it's fixed.
+    failed: bool,
+    changes: Receiver<notify::Result<notify::Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+/// What the functions registered with the engine work on. The scene is swapped in for the length of each hook.
+#[derive(Default)]
+struct Host {
+    scene: Scene,
+    dt: f32,
+    stop: Option<String>,
+}
+
+impl Host {
+    fn body(&mut self, ball: INT) -> Result<&mut PhysicsBody, Box<EvalAltResult>> {
+        let count = self.scene.physics_bodies.len();
+        (usize::try_from(ball).ok())
+            .and_then(|i| self.scene.physics_bodies.get_mut(i))
+            .ok_or_else(|| format!("there is no ball {ball}, only {count}").into())
+    }
+
+    /// Changes ball `ball` and wakes it, along with whatever it was resting on, so the change isn't lost to sleep.
+    fn disturb(&mut self, ball: INT, change: impl FnOnce(&mut PhysicsBody)) -> Result<(), Box<EvalAltResult>> {
+        change(self.body(ball)?);
+        self.scene.wake(ball as usize);
+        Ok(())
+    }
+
+    fn add_ball(&mut self, radius: f32, pos: Vec3, mass: Mass, color: Vec4) -> Result<INT, Box<EvalAltResult>> {
+        if radius <= 0.0 {
+            return Err(format!("balls need a positive radius, not {radius}").into());
+        }
+        if let Mass::Kilograms(mass) = mass {
+            if mass <= 0.0 {
+                return Err(format!("balls need a positive mass, not {mass}").into());
+            }
+        }
+        Ok(self.scene.add_ball_with_mass(radius, pos, color, mass) as INT)
+    }
+}
+
+/// Why a script couldn't be loaded or run.
+#[derive(Debug)]
+pub enum ScriptError {
+    Read(PathBuf, std::io::Error),
+    Compile(rhai::ParseError),
+    /// A hook failed while running.
+    Run(Box<EvalAltResult>),
+    Watch(notify::Error),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "unable to read {}: {e}", path.display()),
+            Self::Compile(e) => write!(f, "{e}"),
+            Self::Run(e) => write!(f, "{e}"),
+            Self::Watch(e) => write!(f, "unable to watch the script for changes: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read(_, e) => Some(e),
+            Self::Compile(e) => Some(e),
+            Self::Run(e) => Some(e),
+            Self::Watch(e) => Some(e),
+        }
+    }
+}
+
+impl Script {
+    /// Compiles the script at `path` and starts watching it for changes.
+    pub fn load(path: &Path) -> Result<Self, ScriptError> {
+        let host = Rc::new(RefCell::new(Host::default()));
+        let mut engine = Engine::new();
+        register_vec3(&mut engine);
+        register_scene(&mut engine, &host);
+        let ast = compile(&engine, path)?;
+
+        // Editors often save by replacing the file, which a watch on the file itself would miss
+        let (sender, changes) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(ScriptError::Watch)?;
+        let directory = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(ScriptError::Watch)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            engine,
+            ast,
+            host,
+            this: Map::new().into(),
+            failed: false,
+            changes,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file changed since the last time this was asked.
+    pub fn changed(&mut self) -> bool {
+        let name = self.path.file_name();
+        let mut changed = false;
+        for event in self.changes.try_iter().flatten() {
+            let written = event.kind.is_create() || event.kind.is_modify();
+            changed |= written && event.paths.iter().any(|p| p.file_name() == name);
+        }
+        changed
+    }
+
+    /// Compiles the file again, keeping the old script if the new one doesn't compile. The script's state is cleared
+    /// either way, ready for `setup` on a fresh scene.
+    pub fn reload(&mut self) -> Result<(), ScriptError> {
+        self.this = Map::new().into();
+        self.failed = false;
+        self.host.borrow_mut().stop = None;
+        self.ast = compile(&self.engine, &self.path)?;
+        Ok(())
+    }
+
+    /// Runs the `setup` hook on `scene`.
+    pub fn setup(&mut self, scene: &mut Scene) -> Result<(), ScriptError> {
+        self.call("setup", scene, 0.0)
+    }
+
+    /// Runs the `step` hook on `scene`, before it's stepped by `dt`. Breaks with the reason given once the script
+    /// calls `stop`.
+    pub fn step(&mut self, scene: &mut Scene, dt: f32) -> Result<ControlFlow<String>, ScriptError> {
+        if !self.failed {
+            self.call("step", scene, dt)?;
+        }
+        Ok(match self.host.borrow_mut().stop.take() {
+            Some(reason) => ControlFlow::Break(reason),
+            None => ControlFlow::Continue(()),
+        })
+    }
+
+    fn call(&mut self, hook: &str, scene: &mut Scene, dt: f32) -> Result<(), ScriptError> {
+        if !self.ast.iter_functions().any(|f| f.name == hook && f.params.is_empty()) {
+            return Ok(());
+        }
+        std::mem::swap(&mut self.host.borrow_mut().scene, scene);
+        self.host.borrow_mut().dt = dt;
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.this);
+        let result = (self.engine).call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, hook, ());
+        std::mem::swap(&mut self.host.borrow_mut().scene, scene);
+
+        self.failed = result.is_err();
+        result.map(|_| ()).map_err(ScriptError::Run)
+    }
+}
+
+fn compile(engine: &Engine, path: &Path) -> Result<AST, ScriptError> {
+    let source = std::fs::read_to_string(path).map_err(|e| ScriptError::Read(path.to_owned(), e))?;
+    let mut ast = engine.compile(source).map_err(ScriptError::Compile)?;
+    ast.set_source(path.to_string_lossy().as_ref());
+    Ok(ast)
+}
+
+fn register_vec3(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Vec3>("Vec3")
+        .register_fn("vec3", Vec3::new)
+        .register_get_set("x", |v: &mut Vec3| v.x, |v: &mut Vec3, x: f32| v.x = x)
+        .register_get_set("y", |v: &mut Vec3| v.y, |v: &mut Vec3, y: f32| v.y = y)
+        .register_get_set("z", |v: &mut Vec3| v.z, |v: &mut Vec3, z: f32| v.z = z)
+        .register_fn("+", |a: Vec3, b: Vec3| a + b)
+        .register_fn("-", |a: Vec3, b: Vec3| a - b)
+        .register_fn("-", |v: Vec3| -v)
+        .register_fn("*", |v: Vec3, s: f32| v * s)
+        .register_fn("*", |s: f32, v: Vec3| s * v)
+        .register_fn("/", |v: Vec3, s: f32| v / s)
+        .register_fn("==", |a: Vec3, b: Vec3| a == b)
+        .register_fn("length", |v: &mut Vec3| v.length())
+        .register_fn("normalize", |v: &mut Vec3| v.normalize_or_zero())
+        .register_fn("dot", |a: &mut Vec3, b: Vec3| a.dot(b))
+        .register_fn("distance", |a: &mut Vec3, b: Vec3| a.distance(b))
+        .register_fn("to_string", |v: &mut Vec3| v.to_string())
+        .register_fn("to_debug", |v: &mut Vec3| format!("{v:?}"));
+}
+
+fn register_scene(engine: &mut Engine, host: &Rc<RefCell<Host>>) {
+    let h = host.clone();
+    engine.register_fn("ball_count", move || h.borrow().scene.physics_bodies.len() as INT);
+
+    let h = host.clone();
+    engine.register_fn("add_ball", move |radius: f32, pos: Vec3| {
+        h.borrow_mut().add_ball(radius, pos, Mass::Kilograms(1.0), Vec4::ONE)
+    });
+    let h = host.clone();
+    engine.register_fn("add_ball", move |radius: f32, pos: Vec3, mass: f32| {
+        h.borrow_mut().add_ball(radius, pos, Mass::Kilograms(mass), Vec4::ONE)
+    });
+    let h = host.clone();
+    engine.register_fn("add_ball", move |radius: f32, pos: Vec3, mass: f32, color: Vec3| {
+        h.borrow_mut()
+            .add_ball(radius, pos, Mass::Kilograms(mass), color.extend(1.0))
+    });
+    let h = host.clone();
+    engine.register_fn("add_fixed_ball", move |radius: f32, pos: Vec3| {
+        h.borrow_mut().add_ball(radius, pos, Mass::Immovable, Vec4::ONE)
+    });
+
+    let h = host.clone();
+    engine.register_fn("pos", move |ball: INT| h.borrow_mut().body(ball).map(|b| b.pos));
+    let h = host.clone();
+    engine.register_fn("velocity", move |ball: INT| {
+        h.borrow_mut().body(ball).map(|b| b.velocity)
+    });
+    let h = host.clone();
+    engine.register_fn("mass", move |ball: INT| h.borrow_mut().body(ball).map(|b| b.mass));
+    let h = host.clone();
+    engine.register_fn("radius", move |ball: INT| h.borrow_mut().body(ball).map(|b| b.radius));
+
+    let h = host.clone();
+    engine.register_fn("set_pos", move |ball: INT, pos: Vec3| {
+        h.borrow_mut().disturb(ball, |b| b.pos = pos)
+    });
+    let h = host.clone();
+    engine.register_fn("set_velocity", move |ball: INT, velocity: Vec3| {
+        h.borrow_mut().disturb(ball, |b| b.velocity = velocity)
+    });
+    let h = host.clone();
+    engine.register_fn("apply_force", move |ball: INT, force: Vec3| {
+        let mut host = h.borrow_mut();
+        let dt = host.dt;
+        // Immovable balls ignore forces, which would come out as NaN divided by their infinite mass
+        host.disturb(ball, |b| {
+            if !b.is_immovable() {
+                b.velocity += force / b.mass * dt;
+            }
+        })
+    });
+    let h = host.clone();
+    engine.register_fn("explode", move |center: Vec3, strength: f32, radius: f32| {
+        h.borrow_mut().scene.apply_explosion(center, strength, radius)
+    });
+
+    let h = host.clone();
+    engine.register_fn("gravity", move || h.borrow().scene.gravity);
+    let h = host.clone();
+    engine.register_fn("set_gravity", move |gravity: Vec3| {
+        let mut host = h.borrow_mut();
+        host.scene.gravity = gravity;
+        // Resting balls don't notice gravity changing, like with the G key
+        host.scene.wake_all();
+    });
+    let h = host.clone();
+    engine.register_fn("time", move || h.borrow().scene.time);
+    let h = host.clone();
+    engine.register_fn("steps", move || h.borrow().scene.steps as INT);
+
+    let h = host.clone();
+    engine.register_fn("stop", move |reason: &str| {
+        h.borrow_mut().stop = Some(reason.to_owned())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::demos::bordered_scene;
+    use crate::gpu_physics::software_device;
+    use crate::rendering::draw_dynamic;
+    use crate::DT;
+    use std::time::{Duration, Instant};
+
+    /// Writes `source` to a file of its own, so tests running at the same time don't see each other's changes.
+    fn script_file(name: &str, source: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("silly-goose-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("script.rhai");
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    /// Steps `scene` with the script until it stops, returning the reason.
+    fn run(script: &mut Script, scene: &mut Scene, max_steps: usize) -> Option<String> {
+        for _ in 0..max_steps {
+            if let ControlFlow::Break(reason) = script.step(scene, DT).unwrap() {
+                return Some(reason);
+            }
+            scene.update_physics(DT);
+        }
+        None
+    }
+
+    #[test]
+    fn example_scripts_run() {
+        for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/scripts")).unwrap() {
+            let path = entry.unwrap().path();
+            let mut script = Script::load(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            let mut scene = bordered_scene();
+            script.setup(&mut scene).unwrap();
+            run(&mut script, &mut scene, 2000);
+            assert!(!scene.physics_bodies.is_empty(), "{} added no balls", path.display());
+        }
+    }
+
+    #[test]
+    fn hooks_drive_the_scene_until_stopped() {
+        let path = script_file(
+            "hooks",
+            r#"
+            fn setup() {
+                this.ball = add_ball(0.05, vec3(0.0, 0.0, 0.0), 2.0);
+                add_fixed_ball(0.1, vec3(0.5, 0.0, 0.0));
+                set_gravity(vec3(0.0, 0.0, 0.0));
+            }
+
+            fn step() {
+                // Constant force towards +x, until the ball gets halfway to the fixed one
+                apply_force(this.ball, vec3(4.0, 0.0, 0.0));
+                if pos(this.ball).x > 0.25 {
+                    stop(`reached ${pos(this.ball).x} after ${steps()} steps`);
+                }
+            }
+            "#,
+        );
+        let mut script = Script::load(&path).unwrap();
+        let mut scene = bordered_scene();
+        script.setup(&mut scene).unwrap();
+        assert_eq!(scene.physics_bodies.len(), 2);
+        assert!(scene.physics_bodies[1].is_immovable());
+
+        let reason = run(&mut script, &mut scene, 2000).expect("script never stopped");
+        assert!(reason.starts_with("reached 0.25"), "{reason}");
+        // Accelerating at 2 m/s², a quarter of a metre takes half a second
+        assert!((scene.time - 0.5).abs() < 0.01, "took {}", scene.time);
+    }
+
+    #[test]
+    fn broken_scripts_are_reported_once() {
+        let path = script_file("broken", "fn step() { pos(3) }");
+        let mut script = Script::load(&path).unwrap();
+        let mut scene = bordered_scene();
+        let error = script.step(&mut scene, DT).unwrap_err().to_string();
+        assert!(error.contains("there is no ball 3, only 0"), "{error}");
+        assert!(script.step(&mut scene, DT).is_ok(), "failed script kept running");
+
+        std::fs::write(&path, "fn step( {").unwrap();
+        assert!(matches!(script.reload(), Err(ScriptError::Compile(_))));
+        assert!(matches!(
+            Script::load(&path.with_file_name("missing.rhai")),
+            Err(ScriptError::Read(..))
+        ));
+    }
+
+    #[test]
+    fn empty_setup_leaves_a_scene_that_still_draws() {
+        let path = script_file("empty", "fn setup() {} fn step() {}");
+        let mut script = Script::load(&path).unwrap();
+        let mut scene = bordered_scene();
+        script.setup(&mut scene).unwrap();
+        assert_eq!(run(&mut script, &mut scene, 100), None);
+        assert!(scene.physics_bodies.is_empty());
+        if let Some((device, queue)) = software_device() {
+            draw_dynamic(&device, &queue, &scene);
+        }
+    }
+
+    #[test]
+    fn notices_changes_and_reloads() {
+        let path = script_file("reload", "fn setup() { add_ball(0.05, vec3(0.0, 0.0, 0.0)); }");
+        let mut script = Script::load(&path).unwrap();
+        assert!(!script.changed());
+
+        std::fs::write(
+            &path,
+            "fn setup() { add_ball(0.05, vec3(0.0, 0.0, 0.0)); add_ball(0.05, vec3(0.2, 0.0, 0.0)); }",
+        )
+        .unwrap();
+        let start = Instant::now();
+        while !script.changed() {
+            assert!(start.elapsed() < Duration::from_secs(5), "change never noticed");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        script.reload().unwrap();
+        let mut scene = bordered_scene();
+        script.setup(&mut scene).unwrap();
+        assert_eq!(scene.physics_bodies.len(), 2);
+    }
+}
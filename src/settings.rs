@@ -0,0 +1,64 @@
+use crate::physics::NonFinitePolicy;
+use std::path::PathBuf;
+
+/// Options that can be changed at startup without recompiling.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Requested samples per pixel. The closest count supported by the adapter is used.
+    pub msaa_samples: u32,
+    /// Wait for vertical blank before presenting. Disabling this picks the lowest latency mode the surface offers.
+    pub vsync: bool,
+    /// Keep stepping physics while the window is minimized or hidden. Rendering is paused either way.
+    pub simulate_while_minimized: bool,
+    /// What to do with balls whose position or velocity goes non-finite.
+    pub non_finite_policy: NonFinitePolicy,
+    /// TOML file to load the starting scene from, instead of the sandbox.
+    pub scene: Option<PathBuf>,
+    /// Rhai script to set up the scene and run every step. Needs the `scripting` feature.
+    pub script: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            msaa_samples: 4,
+            vsync: false,
+            simulate_while_minimized: true,
+            non_finite_policy: NonFinitePolicy::default(),
+            scene: None,
+            script: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Parses `--msaa <samples>`, `--vsync`, `--no-vsync`, `--pause-when-minimized`, `--quarantine`, `--strict`,
+    /// `--scene <path>` and `--script <path>`. Anything else is reported and ignored.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut settings = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--msaa" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(samples) => settings.msaa_samples = samples,
+                    None => eprintln!("--msaa expects a sample count"),
+                },
+                "--vsync" => settings.vsync = true,
+                "--no-vsync" => settings.vsync = false,
+                "--pause-when-minimized" => settings.simulate_while_minimized = false,
+                "--quarantine" => settings.non_finite_policy = NonFinitePolicy::Quarantine,
+                "--strict" => settings.non_finite_policy = NonFinitePolicy::Strict,
+                "--scene" => match args.next() {
+                    Some(path) => settings.scene = Some(path.into()),
+                    None => eprintln!("--scene expects a path"),
+                },
+                "--script" => match args.next() {
+                    Some(_) if !cfg!(feature = "scripting") => eprintln!("--script needs the scripting feature"),
+                    Some(path) => settings.script = Some(path.into()),
+                    None => eprintln!("--script expects a path"),
+                },
+                _ => eprintln!("Ignoring unknown argument {arg}"),
+            }
+        }
+        settings
+    }
+}
@@ -1,3 +1,13 @@
+// Set when the surface format isn't sRGB, in which case the hardware won't encode the output for us
+override ENCODE_SRGB: bool = false;
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec4<f32>,
@@ -16,7 +26,26 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.color = model.color;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.normal = model.normal;
+    return out;
+}
+
+struct InstanceInput {
+    @location(3) center: vec3<f32>,
+    @location(4) radius: f32,
+    @location(5) color: vec4<f32>,
+};
+
+// Balls simulated on the GPU, drawn as instances of a unit sphere
+@vertex
+fn vs_instanced(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.color = instance.color;
+    out.clip_position = camera.view_proj * vec4<f32>(instance.center + model.position * instance.radius, 1.0);
     out.normal = model.normal;
     return out;
 }
@@ -27,5 +56,23 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let diff = max(dot(in.normal, light_dir), 0.0);
     let ambient = 0.1;
     let color = in.color.rgb * (diff + ambient);
+    if ENCODE_SRGB {
+        return vec4(linear_to_srgb(color), in.color.a);
+    }
     return vec4(color, in.color.a);
 }
+
+// Unlit, for lines that have no meaningful normal
+@fragment
+fn fs_flat(in: VertexOutput) -> @location(0) vec4<f32> {
+    if ENCODE_SRGB {
+        return vec4(linear_to_srgb(in.color.rgb), in.color.a);
+    }
+    return in.color;
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3(0.0031308));
+}